@@ -0,0 +1,368 @@
+//! `tock bar`: the timer in the formats status bars read.
+//!
+//! Bars show whole minutes, so with `--watch` a new line is only written when
+//! the displayed minute changes or the state file does (a new phase, a pause).
+//! On Linux changes to the state file are picked up through inotify; elsewhere
+//! the file is re-read once a second.
+
+use crate::{now_unix, prompt, strftime_local, Mode, State};
+use clap::ValueEnum;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const IDLE_TEXT: &str = "⏲ idle";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Protocol {
+    /// One JSON object per line (`return-type: json`)
+    Waybar,
+    /// full_text, short_text and color lines; one line per update with --watch
+    I3blocks,
+    /// Text with %{F} color tags (`tail = true` for --watch)
+    Polybar,
+    /// Text with #[fg] style tags for `status-right`
+    Tmux,
+}
+
+/// Which of the bar's styles applies. Also the Waybar `class`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Class {
+    Work,
+    Break,
+    Paused,
+    Scheduled,
+    /// Over and waiting for `tock next`.
+    Overdue,
+    Idle,
+}
+
+impl Class {
+    fn of(state: Option<&State>) -> Class {
+        match state {
+            None => Class::Idle,
+            Some(state) if state.paused.is_some() => Class::Paused,
+            Some(state) if state.scheduled => Class::Scheduled,
+            Some(state) if state.awaiting_ack => Class::Overdue,
+            Some(state) if state.mode == Mode::Work => Class::Work,
+            Some(_) => Class::Break,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Class::Work => "work",
+            Class::Break => "break",
+            Class::Paused => "paused",
+            Class::Scheduled => "scheduled",
+            Class::Overdue => "overdue",
+            Class::Idle => "idle",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Class::Work => "#e06c75",
+            Class::Break => "#98c379",
+            Class::Paused => "#e5c07b",
+            Class::Scheduled => "#61afef",
+            Class::Overdue => "#d19a66",
+            Class::Idle => "#abb2bf",
+        }
+    }
+}
+
+/// Prints the bar output once, or with `watch` every time it changes until the
+/// bar closes the pipe.
+pub(crate) fn bar(protocol: Protocol, watch: bool, state_path: &Path) -> Result<i32, String> {
+    let mut out = io::stdout();
+    if !watch {
+        let output = render(protocol, prompt::live_state().as_ref(), now_unix(), false)?;
+        write_out(&mut out, &output)?;
+        return Ok(0);
+    }
+
+    let changes = StateChanges::open(state_path)?;
+    let mut last = String::new();
+    loop {
+        let state = prompt::live_state();
+        let now = now_unix();
+        let output = render(protocol, state.as_ref(), now, true)?;
+        if output != last {
+            if write_out(&mut out, &output).is_err() {
+                // The bar went away.
+                return Ok(0);
+            }
+            last = output;
+        }
+        changes.wait(next_minute_in(state.as_ref(), now));
+    }
+}
+
+fn render(
+    protocol: Protocol,
+    state: Option<&State>,
+    now: i64,
+    watch: bool,
+) -> Result<String, String> {
+    let class = Class::of(state);
+    let text = match state {
+        Some(state) => short_text(state, now),
+        None => IDLE_TEXT.to_string(),
+    };
+    let output = match protocol {
+        Protocol::Waybar => {
+            let tooltip = match state {
+                Some(state) => tooltip(state, now)?,
+                None => "No timer running".to_string(),
+            };
+            let percentage = state.map_or(0, |state| (state.progress(now) * 100.0).round() as u64);
+            let json = serde_json::json!({
+                "text": text,
+                "tooltip": tooltip,
+                "class": class.as_str(),
+                "percentage": percentage,
+            });
+            format!("{json}\n")
+        }
+        Protocol::I3blocks if watch => format!("{text}\n"),
+        Protocol::I3blocks => format!("{text}\n{text}\n{0}\n", class.color()),
+        Protocol::Polybar => format!("%{{F{0}}}{text}%{{F-}}\n", class.color()),
+        Protocol::Tmux => format!("#[fg={0}]{text}#[default]\n", class.color()),
+    };
+    Ok(output)
+}
+
+/// `🍅 24m 1/4`: whole minutes left (or until a scheduled start), rounded up so
+/// `0m` never shows while the timer is still running. A phase waiting for
+/// `tock next` shows how long it has been over instead, e.g. `🍅 +3m 1/4`.
+fn short_text(state: &State, now: i64) -> String {
+    let mut text = match state.overdue_secs(now) {
+        Some(overdue_secs) => format!("{0} +{1}m", prompt::icon(state), overdue_secs / 60),
+        None => format!(
+            "{0} {1}m",
+            prompt::icon(state),
+            state.countdown_secs(now).div_ceil(60)
+        ),
+    };
+    if let Some(sequence) = &state.sequence {
+        text.push_str(&format!(" {0}/{1}", sequence.set(), sequence.sets()));
+    }
+    text
+}
+
+fn tooltip(state: &State, now: i64) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let mut phase = state.mode.to_string();
+    if let Some(sequence) = &state.sequence {
+        phase.push_str(&format!(" {0}/{1}", sequence.set(), sequence.sets()));
+    }
+    match state.paused {
+        None if state.scheduled => lines.push(format!(
+            "{phase}, starts at {0}",
+            strftime_local(state.start_ts, "%H:%M")?
+        )),
+        None if state.awaiting_ack => lines.push(format!(
+            "{phase}, over since {0}; `tock next` moves on",
+            strftime_local(state.end_ts, "%H:%M")?
+        )),
+        Some(_) => lines.push(format!(
+            "{phase}, paused with {0}m left",
+            state.remaining_secs(now).div_ceil(60)
+        )),
+        None => lines.push(format!(
+            "{phase}, ends at {0}",
+            strftime_local(state.end_ts, "%H:%M")?
+        )),
+    }
+    if let Some(task) = &state.meta.task {
+        lines.push(format!("task: {task}"));
+    }
+    if !state.meta.tags.is_empty() {
+        lines.push(format!("tags: {0}", state.meta.tags.join(", ")));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// How long until the displayed minute changes. Idle and paused timers only
+/// change when the state file does.
+fn next_minute_in(state: Option<&State>, now: i64) -> Option<Duration> {
+    let state = state.filter(|state| state.paused.is_none())?;
+    if let Some(overdue_secs) = state.overdue_secs(now) {
+        return Some(Duration::from_secs(60 - overdue_secs % 60));
+    }
+    let remaining = state.countdown_secs(now);
+    let secs = match remaining % 60 {
+        0 => 60,
+        rest => rest,
+    };
+    Some(Duration::from_secs(secs.min(remaining.max(1))))
+}
+
+fn write_out(out: &mut io::Stdout, s: &str) -> Result<(), String> {
+    out.write_all(s.as_bytes())
+        .and_then(|()| out.flush())
+        .map_err(|e| format!("failed to write to stdout: {e}"))
+}
+
+/// Wakes up when anything in the state directory changes.
+#[cfg(target_os = "linux")]
+struct StateChanges {
+    fd: i32,
+}
+
+#[cfg(target_os = "linux")]
+impl StateChanges {
+    fn open(state_path: &Path) -> Result<StateChanges, String> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = state_path
+            .parent()
+            .ok_or_else(|| format!("invalid state path {state_path:?}"))?;
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create state dir {dir:?}: {e}"))?;
+        let dir_c = std::ffi::CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| format!("invalid state dir {dir:?}: {e}"))?;
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd == -1 {
+            return Err(format!(
+                "failed to watch {dir:?}: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        // state.json is replaced by rename, so watch the directory, not the file.
+        let mask = libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_DELETE | libc::IN_CLOSE_WRITE;
+        if unsafe { libc::inotify_add_watch(fd, dir_c.as_ptr(), mask) } == -1 {
+            let err = io::Error::last_os_error();
+            unsafe {
+                libc::close(fd);
+            }
+            return Err(format!("failed to watch {dir:?}: {err}"));
+        }
+        Ok(StateChanges { fd })
+    }
+
+    /// Sleeps until something changes or `timeout` passes (forever if `None`).
+    fn wait(&self, timeout: Option<Duration>) {
+        let timeout_ms = match timeout {
+            Some(timeout) => timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+            None => -1,
+        };
+        let mut pfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe {
+            libc::poll(&mut pfd, 1, timeout_ms);
+        }
+        let mut buf = [0u8; 4096];
+        while unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct StateChanges;
+
+#[cfg(not(target_os = "linux"))]
+impl StateChanges {
+    fn open(_state_path: &Path) -> Result<StateChanges, String> {
+        Ok(StateChanges)
+    }
+
+    fn wait(&self, timeout: Option<Duration>) {
+        let second = Duration::from_secs(1);
+        std::thread::sleep(timeout.map_or(second, |timeout| timeout.min(second)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Sequence;
+    use crate::tests::{in_tz, running, CET, JAN_10_10AM};
+    use crate::Pause;
+
+    #[test]
+    fn render_writes_each_protocol() {
+        in_tz(CET, || {
+            let mut state = running(Mode::Work, JAN_10_10AM, 1500);
+            state.sequence = Some(Sequence::parse("(25w 5b)x4").unwrap());
+            state.meta.task = Some("report".to_string());
+            let state = Some(&state);
+            let now = JAN_10_10AM + 90;
+
+            assert_eq!(render(Protocol::Waybar, state, now, false).unwrap(), "{\"class\":\"work\",\"percentage\":6,\"text\":\"🍅 24m 1/4\",\"tooltip\":\"work 1/4, ends at 10:25\\ntask: report\"}\n");
+            assert_eq!(
+                render(Protocol::I3blocks, state, now, false).unwrap(),
+                "🍅 24m 1/4\n🍅 24m 1/4\n#e06c75\n"
+            );
+            assert_eq!(
+                render(Protocol::I3blocks, state, now, true).unwrap(),
+                "🍅 24m 1/4\n"
+            );
+            assert_eq!(
+                render(Protocol::Polybar, state, now, false).unwrap(),
+                "%{F#e06c75}🍅 24m 1/4%{F-}\n"
+            );
+            assert_eq!(
+                render(Protocol::Tmux, state, now, false).unwrap(),
+                "#[fg=#e06c75]🍅 24m 1/4#[default]\n"
+            );
+        });
+    }
+
+    #[test]
+    fn render_shows_idle_paused_and_overdue_timers() {
+        in_tz(CET, || {
+            let now = JAN_10_10AM + 90;
+            assert_eq!(render(Protocol::Waybar, None, now, false).unwrap(), "{\"class\":\"idle\",\"percentage\":0,\"text\":\"⏲ idle\",\"tooltip\":\"No timer running\"}\n");
+            assert_eq!(
+                render(Protocol::Tmux, None, now, false).unwrap(),
+                "#[fg=#abb2bf]⏲ idle#[default]\n"
+            );
+
+            let mut paused = running(Mode::Break, JAN_10_10AM, 300);
+            paused.paused = Some(Pause {
+                since: JAN_10_10AM + 60,
+                remaining_secs: 240,
+            });
+            assert_eq!(render(Protocol::Waybar, Some(&paused), now, false).unwrap(), "{\"class\":\"paused\",\"percentage\":20,\"text\":\"⏸ 4m\",\"tooltip\":\"break, paused with 4m left\"}\n");
+
+            let mut overdue = running(Mode::Work, JAN_10_10AM - 1500, 1500);
+            overdue.awaiting_ack = true;
+            assert_eq!(
+                render(Protocol::Polybar, Some(&overdue), now + 120, false).unwrap(),
+                "%{F#d19a66}🍅 +3m%{F-}\n"
+            );
+        });
+    }
+
+    #[test]
+    fn next_minute_in_waits_for_the_displayed_minute() {
+        let secs = |state: Option<&State>, now| next_minute_in(state, now).map(|d| d.as_secs());
+        let state = running(Mode::Work, 1000, 1500);
+        // 1490s left shows as 25m until 1440s are left.
+        assert_eq!(secs(Some(&state), 1010), Some(50));
+        assert_eq!(secs(Some(&state), 1060), Some(60));
+        assert_eq!(secs(Some(&state), 2490), Some(10));
+        assert_eq!(secs(Some(&state), 2500), Some(1));
+
+        let mut paused = state.clone();
+        paused.paused = Some(Pause {
+            since: 1010,
+            remaining_secs: 1490,
+        });
+        assert_eq!(secs(Some(&paused), 1100), None);
+        assert_eq!(secs(None, 1100), None);
+
+        let mut overdue = state.clone();
+        overdue.awaiting_ack = true;
+        assert_eq!(secs(Some(&overdue), 2520), Some(40));
+
+        let mut scheduled = state;
+        scheduled.scheduled = true;
+        assert_eq!(secs(Some(&scheduled), 970), Some(30));
+    }
+}
@@ -0,0 +1,419 @@
+//! Time budgets for projects, e.g. ten hours of client work a week.
+//!
+//! A session belongs to a project when its task or one of its tags is the
+//! project's name. What counts against a budget is the active time of the
+//! project's work sessions in the current day, week (Monday to Sunday) or
+//! month, however they ended, plus the session running now.
+
+use crate::config::{self, toml_key, toml_string, BudgetConfig};
+use crate::log::{self, EndReason, LogEntry};
+use crate::notify::Notification;
+use crate::{
+    add_local_days, config_path, current_state, format_length, local_day_start, local_mktime,
+    local_tm, local_week_start, log_path, now_unix, parse_span, Meta, Mode, State,
+};
+use clap::{Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Shares of a budget, in percent, that a completed session gets a
+/// notification for crossing.
+const THRESHOLDS: [u64; 2] = [80, 100];
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum BudgetCommand {
+    /// Set a project's budget, e.g. `acme 10h --per week`
+    Set {
+        /// Matched against each session's task and tags
+        project: String,
+        /// Active work time allowed, e.g. 10h or 90m
+        #[arg(value_parser = parse_limit)]
+        limit: u64,
+        #[arg(long, value_enum, default_value_t = Period::Week)]
+        per: Period,
+    },
+    /// Remove a project's budget
+    Clear { project: String },
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Period {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl Period {
+    fn as_str(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+
+    fn adjective(self) -> &'static str {
+        match self {
+            Period::Day => "daily",
+            Period::Week => "weekly",
+            Period::Month => "monthly",
+        }
+    }
+
+    fn current(self) -> &'static str {
+        match self {
+            Period::Day => "today",
+            Period::Week => "this week",
+            Period::Month => "this month",
+        }
+    }
+
+    /// The half-open range of the period containing `ts`.
+    fn range(self, ts: i64) -> Result<(i64, i64), String> {
+        match self {
+            Period::Day => {
+                let from = local_day_start(ts)?;
+                Ok((from, add_local_days(from, 1)?))
+            }
+            Period::Week => {
+                let from = local_week_start(ts)?;
+                Ok((from, add_local_days(from, 7)?))
+            }
+            Period::Month => {
+                let mut tm = local_tm(ts)?;
+                tm.tm_mday = 1;
+                tm.tm_hour = 0;
+                tm.tm_min = 0;
+                tm.tm_sec = 0;
+                let from = local_mktime(tm)?;
+                tm.tm_mon += 1;
+                Ok((from, local_mktime(tm)?))
+            }
+        }
+    }
+}
+
+/// Parses a budget limit: any length `parse_span` reads, with no upper bound.
+pub(crate) fn parse_limit(s: &str) -> Result<u64, String> {
+    let secs = parse_span(s)?;
+    if secs == 0 {
+        return Err("budget must be > 0".to_string());
+    }
+    Ok(secs)
+}
+
+/// `project` as budgets are keyed: trimmed, like the task and tags it is matched with.
+fn project_name(project: &str) -> Result<&str, String> {
+    let project = project.trim();
+    if project.is_empty() {
+        return Err("project name must not be empty".to_string());
+    }
+    Ok(project)
+}
+
+/// How much of a budget is used in its current period.
+struct Usage<'a> {
+    budget: &'a BudgetConfig,
+    used_secs: u64,
+}
+
+impl Usage<'_> {
+    /// E.g. `7h30m of 10h this week (75%, 2h30m left)`.
+    fn summary(&self) -> String {
+        let limit = self.budget.limit;
+        let balance = match self.used_secs.checked_sub(limit) {
+            Some(over) if over >= 60 => format!("{0} over", whole_minutes(over)),
+            Some(_) => "none left".to_string(),
+            None => format!("{0} left", whole_minutes(limit - self.used_secs)),
+        };
+        format!(
+            "{0} of {1} {2} ({3}%, {balance})",
+            whole_minutes(self.used_secs),
+            format_length(limit),
+            self.budget.per.current(),
+            percent(self.used_secs, limit)
+        )
+    }
+}
+
+pub(crate) fn budget_command(command: Option<BudgetCommand>) -> Result<i32, String> {
+    let path = config_path()?;
+    match command {
+        Some(BudgetCommand::Set {
+            project,
+            limit,
+            per,
+        }) => {
+            let project = project_name(&project)?;
+            let value = format!(
+                "{{ limit = {0}, per = {1} }}",
+                toml_string(&format_length(limit)),
+                toml_string(per.as_str())
+            );
+            config::update(&path, "budget", &toml_key(project), Some(&value))?;
+            println!(
+                "budget for {project}: {0} per {1}",
+                format_length(limit),
+                per.as_str()
+            );
+        }
+        Some(BudgetCommand::Clear { project }) => {
+            let project = project_name(&project)?;
+            if !config::load(&path)?.budget.contains_key(project) {
+                println!("no budget for {project}");
+                return Ok(1);
+            }
+            config::update(&path, "budget", &toml_key(project), None)?;
+            println!("budget for {project} cleared");
+        }
+        None => {
+            let budgets = config::load(&path)?.budget;
+            if budgets.is_empty() {
+                println!("no budgets set");
+                return Ok(1);
+            }
+            let now = now_unix();
+            let running = current_state()?.map(|(_, state)| state);
+            let entries = entries_with(running.as_ref(), now)?;
+            for (project, budget) in &budgets {
+                let usage = usage(&entries, project, budget, now)?;
+                println!("{project}: {0}", usage.summary());
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Lines for `status`: one for each budget the running session counts against.
+pub(crate) fn status_lines(state: &State, now: i64) -> Result<Vec<String>, String> {
+    if state.mode != Mode::Work {
+        return Ok(Vec::new());
+    }
+    let budgets = config::load(&config_path()?)?.budget;
+    let matching: Vec<_> = budgets
+        .iter()
+        .filter(|(project, _)| belongs(&state.meta, project))
+        .collect();
+    if matching.is_empty() {
+        return Ok(Vec::new());
+    }
+    let entries = entries_with(Some(state), now)?;
+    matching
+        .into_iter()
+        .map(|(project, budget)| {
+            let usage = usage(&entries, project, budget, now)?;
+            Ok(format!("budget {project}: {0}", usage.summary()))
+        })
+        .collect()
+}
+
+/// Notifications for the budgets that `ended`, a work session already in the
+/// log, took past one of the `THRESHOLDS`.
+pub(crate) fn notifications(ended: &LogEntry) -> Result<Vec<Notification>, String> {
+    let budgets = config::load(&config_path()?)?.budget;
+    if !budgets.keys().any(|project| belongs(&ended.meta, project)) {
+        return Ok(Vec::new());
+    }
+    let entries = log::read_entries(&log_path()?)?;
+    crossed(ended, &budgets, &entries)
+}
+
+/// `notifications` for `budgets`, with `entries` as the log. Only the highest
+/// threshold crossed gets one, and none that was already reached before `ended`.
+fn crossed(
+    ended: &LogEntry,
+    budgets: &BTreeMap<String, BudgetConfig>,
+    entries: &[LogEntry],
+) -> Result<Vec<Notification>, String> {
+    let mut notifications = Vec::new();
+    for (project, budget) in budgets {
+        if !belongs(&ended.meta, project) {
+            continue;
+        }
+        let after = usage(entries, project, budget, ended.end_ts)?;
+        let (from, to) = budget.per.range(ended.end_ts)?;
+        let before = after
+            .used_secs
+            .saturating_sub(ended.active_secs_within(from, to));
+        let reached = |used_secs: u64, threshold: u64| {
+            used_secs as u128 * 100 >= threshold as u128 * budget.limit as u128
+        };
+        let crossed = THRESHOLDS
+            .iter()
+            .rev()
+            .find(|&&threshold| !reached(before, threshold) && reached(after.used_secs, threshold));
+        let Some(&threshold) = crossed else {
+            continue;
+        };
+        let body = if threshold >= 100 {
+            format!(
+                "{project}: the {0} {1} budget is used up ({2}).",
+                format_length(budget.limit),
+                budget.per.adjective(),
+                whole_minutes(after.used_secs)
+            )
+        } else {
+            format!(
+                "{project}: {0}% of the {1} {2} budget used ({3} left).",
+                percent(after.used_secs, budget.limit),
+                format_length(budget.limit),
+                budget.per.adjective(),
+                whole_minutes(budget.limit.saturating_sub(after.used_secs))
+            )
+        };
+        notifications.push(Notification::budget(threshold, body));
+    }
+    Ok(notifications)
+}
+
+/// The log, plus the session running now as if it ended at `now`.
+fn entries_with(running: Option<&State>, now: i64) -> Result<Vec<LogEntry>, String> {
+    let mut entries = log::read_entries(&log_path()?)?;
+    if let Some(state) = running.filter(|state| !state.scheduled) {
+        entries.push(LogEntry::from_state(state, now, EndReason::Completed));
+    }
+    Ok(entries)
+}
+
+fn usage<'a>(
+    entries: &[LogEntry],
+    project: &str,
+    budget: &'a BudgetConfig,
+    now: i64,
+) -> Result<Usage<'a>, String> {
+    let (from, to) = budget.per.range(now)?;
+    let used_secs = entries
+        .iter()
+        .filter(|entry| entry.mode == Mode::Work && belongs(&entry.meta, project))
+        .map(|entry| entry.active_secs_within(from, to))
+        .sum();
+    Ok(Usage { budget, used_secs })
+}
+
+fn belongs(meta: &Meta, project: &str) -> bool {
+    meta.task.as_deref() == Some(project) || meta.tags.iter().any(|tag| tag == project)
+}
+
+fn percent(used_secs: u64, limit_secs: u64) -> u64 {
+    (used_secs as u128 * 100 / limit_secs.max(1) as u128) as u64
+}
+
+/// A length rounded down to the minute; budgets don't need seconds.
+fn whole_minutes(secs: u64) -> String {
+    if secs < 60 {
+        return "0m".to_string();
+    }
+    format_length(secs - secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbus::Urgency;
+    use crate::tests::{in_tz, session, CET, JAN_10_10AM};
+
+    const HOUR: i64 = 3600;
+
+    fn acme(limit_hours: u64) -> BTreeMap<String, BudgetConfig> {
+        let budget = BudgetConfig {
+            limit: limit_hours * 3600,
+            per: Period::Week,
+        };
+        BTreeMap::from([("acme".to_string(), budget)])
+    }
+
+    /// A work session for acme of `hours`, ending `ago` hours before 10:00 on
+    /// Saturday 2026-01-10.
+    fn worked(hours: i64, ago: i64) -> LogEntry {
+        let end_ts = JAN_10_10AM - ago * HOUR;
+        let mut entry = session(Mode::Work, end_ts - hours * HOUR, end_ts, 0);
+        entry.meta.task = Some("acme".to_string());
+        entry
+    }
+
+    /// The notifications for ending `ended` after `earlier`.
+    fn crossing(limit_hours: u64, earlier: &[LogEntry], ended: LogEntry) -> Vec<Notification> {
+        let mut entries = earlier.to_vec();
+        entries.push(ended.clone());
+        in_tz(CET, || crossed(&ended, &acme(limit_hours), &entries)).unwrap()
+    }
+
+    #[test]
+    fn crossing_a_threshold_notifies_once() {
+        let earlier = [worked(7, 24)];
+        let [notification] = &crossing(10, &earlier, worked(1, 0))[..] else {
+            panic!("expected one notification");
+        };
+        assert_eq!(notification.urgency, Urgency::Normal);
+        assert_eq!(
+            notification.body,
+            "acme: 80% of the 10h weekly budget used (2h left)."
+        );
+        // Already past 80% before this one.
+        let earlier = [worked(7, 48), worked(1, 24)];
+        assert!(crossing(10, &earlier, worked(1, 0)).is_empty());
+    }
+
+    #[test]
+    fn the_higher_threshold_wins() {
+        let [notification] = &crossing(10, &[worked(7, 24)], worked(4, 0))[..] else {
+            panic!("expected one notification");
+        };
+        assert_eq!(notification.urgency, Urgency::Critical);
+        assert_eq!(
+            notification.body,
+            "acme: the 10h weekly budget is used up (11h)."
+        );
+        assert!(crossing(10, &[worked(10, 24)], worked(1, 0)).is_empty());
+    }
+
+    #[test]
+    fn only_the_current_period_and_project_count() {
+        // Last week's hours don't count toward this week.
+        assert!(crossing(10, &[worked(8, 7 * 24)], worked(1, 0)).is_empty());
+        let mut other = worked(1, 0);
+        other.meta.task = Some("other".to_string());
+        assert!(crossing(1, &[], other).is_empty());
+        let mut tagged = worked(1, 0);
+        tagged.meta.task = None;
+        tagged.meta.tags = vec!["acme".to_string()];
+        assert_eq!(crossing(1, &[], tagged).len(), 1);
+    }
+
+    #[test]
+    fn periods_are_local_days_weeks_and_months() {
+        in_tz(CET, || {
+            let jan_10 = JAN_10_10AM - 10 * HOUR;
+            assert_eq!(
+                Period::Day.range(JAN_10_10AM),
+                Ok((jan_10, jan_10 + 24 * HOUR))
+            );
+            let monday = jan_10 - 5 * 24 * HOUR;
+            assert_eq!(
+                Period::Week.range(JAN_10_10AM),
+                Ok((monday, monday + 7 * 24 * HOUR))
+            );
+            let jan_1 = jan_10 - 9 * 24 * HOUR;
+            assert_eq!(
+                Period::Month.range(JAN_10_10AM),
+                Ok((jan_1, jan_1 + 31 * 24 * HOUR))
+            );
+        });
+    }
+
+    #[test]
+    fn december_runs_into_the_next_year() {
+        in_tz(CET, || {
+            let (dec_1, jan_1) = (1796079600, 1798758000);
+            assert_eq!(Period::Month.range(1797332400), Ok((dec_1, jan_1)));
+            assert_eq!(Period::Month.range(jan_1 - 1), Ok((dec_1, jan_1)));
+            assert_eq!(Period::Month.range(jan_1).unwrap().0, jan_1);
+            // March loses an hour to DST.
+            let (mar_1, apr_1) = (1772319600, 1774994400);
+            assert_eq!(Period::Month.range(mar_1 + 100), Ok((mar_1, apr_1)));
+            assert_eq!(apr_1 - mar_1, 31 * 24 * HOUR - HOUR);
+        });
+    }
+}
@@ -0,0 +1,655 @@
+//! The optional config file, `$XDG_CONFIG_HOME/tock/config.toml`.
+//!
+//! ```toml
+//! [defaults]
+//! work = "25m"
+//! break = "5m"
+//!
+//! [profile.deep]
+//! work = "50m"
+//! break = "10m"
+//! sets = 3
+//!
+//! [sequence]
+//! morning = "10w (25w 5b)x3 25w 15lb"
+//!
+//! [notify]
+//! backends = ["desktop", "command"]
+//! command = "ntfy publish pomodoro {body}"
+//!
+//! [goal]
+//! daily = 8
+//! rollover_hour = 4
+//!
+//! [budget]
+//! acme = { limit = "10h", per = "week" }
+//! ```
+
+use crate::budget::{self, Period};
+use crate::sequence::Sequence;
+use crate::{config_path, goal, parse_duration, parse_sets, validate_sets};
+use clap::Subcommand;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Latest hour `goal.rollover_hour` may be.
+const MAX_ROLLOVER_HOUR: u32 = 23;
+/// Picks a profile when `--profile` isn't given.
+const PROFILE_VAR: &str = "TOCK_PROFILE";
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigCommand {
+    /// Print a setting, e.g. `defaults.work` or `profile.deep`
+    Get { key: String },
+    /// Change a setting; the value is read as TOML, or else as a string
+    Set { key: String, value: String },
+    /// Print every setting in the file
+    List,
+    /// Open the file in $VISUAL or $EDITOR, then check it
+    Edit,
+    /// Check the file for errors
+    Validate,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Settings for new timers that nothing else sets.
+    pub(crate) defaults: SessionConfig,
+    /// Named sets of settings, picked with `--profile`.
+    pub(crate) profile: BTreeMap<String, SessionConfig>,
+    /// Named sequences for `tock run-sequence`.
+    pub(crate) sequence: BTreeMap<String, SequenceConfig>,
+    pub(crate) notify: NotifyConfig,
+    pub(crate) goal: GoalConfig,
+    /// Budgets by project name.
+    pub(crate) budget: BTreeMap<String, BudgetConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct NotifyConfig {
+    /// Every notification goes to each of these.
+    pub(crate) backends: Vec<Backend>,
+    /// Shell command template for the `command` backend.
+    pub(crate) command: Option<String>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> NotifyConfig {
+        NotifyConfig {
+            backends: vec![Backend::Desktop],
+            command: None,
+        }
+    }
+}
+
+/// Lengths and cycle settings for a new timer. The command line, `TOCK_*`
+/// variables, the profile and `[defaults]` each give one of these, and each
+/// fills in what the ones before it leave unset.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SessionConfig {
+    #[serde(rename = "work", deserialize_with = "length")]
+    pub(crate) work_secs: Option<u64>,
+    #[serde(rename = "break", deserialize_with = "length")]
+    pub(crate) break_secs: Option<u64>,
+    #[serde(rename = "long_break", deserialize_with = "length")]
+    pub(crate) long_break_secs: Option<u64>,
+    #[serde(deserialize_with = "long_break_every")]
+    pub(crate) long_break_every: Option<u64>,
+    /// Work sessions in a cycle; 1 is a single session.
+    #[serde(deserialize_with = "sets")]
+    pub(crate) sets: Option<u64>,
+    pub(crate) final_long_break: Option<bool>,
+    /// Wait for `tock next` between the phases of a cycle or sequence.
+    pub(crate) manual_advance: Option<bool>,
+}
+
+impl SessionConfig {
+    fn or(self, fallback: SessionConfig) -> SessionConfig {
+        SessionConfig {
+            work_secs: self.work_secs.or(fallback.work_secs),
+            break_secs: self.break_secs.or(fallback.break_secs),
+            long_break_secs: self.long_break_secs.or(fallback.long_break_secs),
+            long_break_every: self.long_break_every.or(fallback.long_break_every),
+            sets: self.sets.or(fallback.sets),
+            final_long_break: self.final_long_break.or(fallback.final_long_break),
+            manual_advance: self.manual_advance.or(fallback.manual_advance),
+        }
+    }
+
+    /// The settings given as `TOCK_WORK`, `TOCK_BREAK`, `TOCK_LONG_BREAK`,
+    /// `TOCK_LONG_BREAK_EVERY`, `TOCK_SETS`, `TOCK_FINAL_LONG_BREAK` and
+    /// `TOCK_MANUAL_ADVANCE`.
+    fn from_env() -> Result<SessionConfig, String> {
+        fn var<T>(
+            name: &str,
+            parse: impl Fn(&str) -> Result<T, String>,
+        ) -> Result<Option<T>, String> {
+            match std::env::var(name) {
+                Ok(value) if !value.is_empty() => {
+                    parse(&value).map(Some).map_err(|e| format!("{name}: {e}"))
+                }
+                _ => Ok(None),
+            }
+        }
+        Ok(SessionConfig {
+            work_secs: var("TOCK_WORK", parse_duration)?,
+            break_secs: var("TOCK_BREAK", parse_duration)?,
+            long_break_secs: var("TOCK_LONG_BREAK", parse_duration)?,
+            long_break_every: var("TOCK_LONG_BREAK_EVERY", parse_long_break_every)?,
+            sets: var("TOCK_SETS", parse_sets)?,
+            final_long_break: var("TOCK_FINAL_LONG_BREAK", parse_bool)?,
+            manual_advance: var("TOCK_MANUAL_ADVANCE", parse_bool)?,
+        })
+    }
+}
+
+/// Completes `cli` from the `TOCK_*` variables, then the profile (`profile`,
+/// or else `$TOCK_PROFILE`), then `[defaults]`.
+pub(crate) fn session_settings(
+    cli: SessionConfig,
+    profile: Option<&str>,
+) -> Result<SessionConfig, String> {
+    let path = config_path()?;
+    let mut config = load(&path)?;
+    let env = SessionConfig::from_env()?;
+    let profile = match profile
+        .map(str::to_string)
+        .or_else(|| std::env::var(PROFILE_VAR).ok())
+    {
+        Some(name) if !name.is_empty() => config
+            .profile
+            .remove(&name)
+            .ok_or_else(|| format!("{0}: no profile named {name:?}", path.display()))?,
+        _ => SessionConfig::default(),
+    };
+    Ok(cli.or(env).or(profile).or(config.defaults))
+}
+
+/// A sequence written as in `tock run-sequence`, e.g. `"10w (25w 5b)x4"`.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct SequenceConfig(pub(crate) Sequence);
+
+impl TryFrom<String> for SequenceConfig {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<SequenceConfig, String> {
+        Sequence::parse(&s).map(SequenceConfig)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct GoalConfig {
+    /// Completed pomodoros a day; no goal when unset.
+    #[serde(deserialize_with = "daily_goal")]
+    pub(crate) daily: Option<u64>,
+    /// Local hour at which a new day begins for the goal and the streak, so
+    /// work shortly after midnight still counts toward the day before.
+    #[serde(deserialize_with = "rollover_hour")]
+    pub(crate) rollover_hour: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BudgetConfig {
+    /// Active work seconds allowed per period; written like `"10h"`.
+    #[serde(deserialize_with = "limit_secs")]
+    pub(crate) limit: u64,
+    #[serde(default)]
+    pub(crate) per: Period,
+}
+
+// Values are checked as they are read, so an error points at the line with the value.
+
+/// A length written like `"25m"`, or as a number of minutes like `25`.
+fn length<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let length = match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(s) => s,
+        toml::Value::Integer(minutes) => minutes.to_string(),
+        _ => return Err(D::Error::custom("expected a length such as \"25m\" or 25")),
+    };
+    parse_duration(&length).map(Some).map_err(D::Error::custom)
+}
+
+fn long_break_every<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let every = u64::deserialize(deserializer)?;
+    check_long_break_every(every)
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+fn sets<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let sets = u64::deserialize(deserializer)?;
+    validate_sets(sets)
+        .map(|()| Some(sets))
+        .map_err(D::Error::custom)
+}
+
+fn daily_goal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let daily = u64::deserialize(deserializer)?;
+    goal::validate_goal(daily)
+        .map(|()| Some(daily))
+        .map_err(D::Error::custom)
+}
+
+fn rollover_hour<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let hour = u32::deserialize(deserializer)?;
+    if hour > MAX_ROLLOVER_HOUR {
+        return Err(D::Error::custom(format!(
+            "must be 0 to {MAX_ROLLOVER_HOUR}"
+        )));
+    }
+    Ok(hour)
+}
+
+fn limit_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let limit = String::deserialize(deserializer)?;
+    budget::parse_limit(&limit).map_err(D::Error::custom)
+}
+
+fn parse_long_break_every(s: &str) -> Result<u64, String> {
+    let every = s.parse().map_err(|_| format!("invalid number {s:?}"))?;
+    check_long_break_every(every)
+}
+
+fn check_long_break_every(every: u64) -> Result<u64, String> {
+    if every == 0 {
+        return Err("long break interval must be > 0".to_string());
+    }
+    Ok(every)
+}
+
+fn parse_bool(s: &str) -> Result<bool, String> {
+    match s {
+        "1" | "true" | "yes" => Ok(true),
+        "0" | "false" | "no" => Ok(false),
+        _ => Err(format!("invalid value {s:?} (expected true or false)")),
+    }
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Backend {
+    /// `osascript` on macOS, `dbus` elsewhere.
+    Desktop,
+    Osascript,
+    Dbus,
+    Command,
+    Bell,
+    None,
+}
+
+/// Reads the config at `path`. A missing file means all defaults.
+pub(crate) fn load(path: &Path) -> Result<Config, String> {
+    parse(path, &read(path)?)
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(format!("failed to read config {path:?}: {e}")),
+    }
+}
+
+fn parse(path: &Path, contents: &str) -> Result<Config, String> {
+    toml::from_str(contents).map_err(|e| located(path, contents, &e))
+}
+
+pub(crate) fn config_command(command: ConfigCommand) -> Result<i32, String> {
+    let path = config_path()?;
+    match command {
+        ConfigCommand::Get { key } => {
+            let root = toml::Value::Table(read_table(&path)?);
+            let value = key
+                .split('.')
+                .try_fold(&root, |value, part| value.as_table()?.get(part));
+            match value {
+                Some(toml::Value::Table(table)) => print_settings(&key, table),
+                Some(toml::Value::String(s)) => println!("{s}"),
+                Some(value) => println!("{value}"),
+                None => {
+                    println!("{key} is not set in {0}", path.display());
+                    return Ok(1);
+                }
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let Some((section, name)) = key
+                .rsplit_once('.')
+                .filter(|(section, name)| !section.is_empty() && !name.is_empty())
+            else {
+                return Err(format!(
+                    "invalid setting {key:?} (expected e.g. defaults.work)"
+                ));
+            };
+            // `50m` is meant as a string; `8`, `true` and `["bell"]` are TOML already.
+            let value = match format!("value = {value}").parse::<toml::Table>() {
+                Ok(_) => value,
+                Err(_) => toml_string(&value),
+            };
+            update(&path, section, &toml_key(name), Some(&value))?;
+            println!("{key} = {value}");
+        }
+        ConfigCommand::List => print_settings("", &read_table(&path)?),
+        ConfigCommand::Edit => {
+            let dir = path
+                .parent()
+                .ok_or_else(|| format!("invalid config path {path:?}"))?;
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create config dir {dir:?}: {e}"))?;
+            let editor = ["VISUAL", "EDITOR"]
+                .iter()
+                .find_map(|name| std::env::var(name).ok().filter(|editor| !editor.is_empty()))
+                .unwrap_or_else(|| "vi".to_string());
+            // Through the shell, so an editor with flags (`code --wait`) works.
+            let status = Command::new("sh")
+                .arg("-c")
+                .arg(format!("{editor} \"$1\""))
+                .arg("sh")
+                .arg(&path)
+                .status()
+                .map_err(|e| format!("failed to run {editor}: {e}"))?;
+            if !status.success() {
+                return Err(format!("{editor} exited with {status}"));
+            }
+            load(&path)?;
+            println!("{0}: ok", path.display());
+        }
+        ConfigCommand::Validate => {
+            load(&path)?;
+            println!("{0}: ok", path.display());
+        }
+    }
+    Ok(0)
+}
+
+/// Prints every value in `table` as a `dotted.key = value` line.
+fn print_settings(prefix: &str, table: &toml::Table) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            toml::Value::Table(inner) => print_settings(&key, inner),
+            value => println!("{key} = {value}"),
+        }
+    }
+}
+
+/// The file as plain TOML, for showing settings as they are written.
+fn read_table(path: &Path) -> Result<toml::Table, String> {
+    let contents = read(path)?;
+    toml::from_str(&contents).map_err(|e| located(path, &contents, &e))
+}
+
+/// A parse error as `file:line:column: message`.
+fn located(path: &Path, contents: &str, e: &toml::de::Error) -> String {
+    let location = match e.span() {
+        Some(span) => {
+            let (line, column) = line_column(contents, span.start);
+            format!("{0}:{line}:{column}", path.display())
+        }
+        None => path.display().to_string(),
+    };
+    format!("{location}: {0}", e.message())
+}
+
+pub(crate) fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+/// `key` as written in a TOML file: quoted unless it is a plain bare key.
+pub(crate) fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+/// Sets `key` in the `[section]` table of the config at `path` to `value`, a
+/// TOML value, or with `None` removes it. The rest of the file, comments
+/// included, stays as it is. Nothing is written unless the result is a valid config.
+pub(crate) fn update(
+    path: &Path,
+    section: &str,
+    key: &str,
+    value: Option<&str>,
+) -> Result<(), String> {
+    let contents = read(path)?;
+    let Some(updated) = edit(&contents, section, key, value) else {
+        return Ok(());
+    };
+    parse(path, &updated).map_err(|e| format!("{e} (not saved)"))?;
+
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("invalid config path {path:?}"))?;
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create config dir {dir:?}: {e}"))?;
+    let tmp = path.with_extension("toml.tmp");
+    fs::write(&tmp, updated).map_err(|e| format!("failed to write config {tmp:?}: {e}"))?;
+    fs::rename(&tmp, path).map_err(|e| format!("failed to save config {path:?}: {e}"))
+}
+
+/// `contents` with `key` in `[section]` set to `value` or removed, as for
+/// `update`; `None` when there is nothing to remove.
+fn edit(contents: &str, section: &str, key: &str, value: Option<&str>) -> Option<String> {
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+
+    // The section's last line (its header if it is empty) and the key's line.
+    let mut in_section = false;
+    let mut section_end = None;
+    let mut key_line = None;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let header = table_header(trimmed);
+        // An `[[array]]` header ends the section too.
+        if header.is_some() || trimmed.starts_with("[[") {
+            in_section = header == Some(section);
+            if in_section {
+                section_end = Some(i);
+            }
+            continue;
+        }
+        if !in_section || trimmed.is_empty() {
+            continue;
+        }
+        section_end = Some(i);
+        let line_key = trimmed.split_once('=').map(|(line_key, _)| line_key.trim());
+        if !trimmed.starts_with('#') && line_key == Some(key) {
+            key_line = Some(i);
+        }
+    }
+
+    let setting = value.map(|value| format!("{key} = {value}"));
+    match (key_line, setting) {
+        (Some(i), Some(setting)) => lines[i] = setting,
+        (Some(i), None) => {
+            lines.remove(i);
+        }
+        (None, None) => return None,
+        (None, Some(setting)) => match section_end {
+            Some(end) => lines.insert(end + 1, setting),
+            None => {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push(format!("[{section}]"));
+                lines.push(setting);
+            }
+        },
+    }
+
+    let mut updated = lines.join("\n");
+    updated.push('\n');
+    Some(updated)
+}
+
+/// The table name in a `[table]` header line; `None` for anything else,
+/// including `[[array]]` headers.
+fn table_header(line: &str) -> Option<&str> {
+    let line = line
+        .split_once('#')
+        .map_or(line, |(before, _)| before)
+        .trim_end();
+    let name = line.strip_prefix('[')?.strip_suffix(']')?;
+    (!name.starts_with('[')).then(|| name.trim())
+}
+
+/// 1-based line and column of byte `offset` in `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before.len(), |i| before.len() - i - 1)
+        + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(name: &str, contents: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("tock-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn edit_inserts_after_the_last_setting_of_the_section() {
+        let contents = "[defaults]\nwork = \"25m\"\n\n[goal]\ndaily = 8\n";
+        assert_eq!(
+            edit(contents, "defaults", "break", Some("\"5m\"")).unwrap(),
+            "[defaults]\nwork = \"25m\"\nbreak = \"5m\"\n\n[goal]\ndaily = 8\n"
+        );
+        assert_eq!(
+            edit("[goal]\n", "goal", "daily", Some("8")).unwrap(),
+            "[goal]\ndaily = 8\n"
+        );
+    }
+
+    #[test]
+    fn edit_replaces_a_setting_and_keeps_comments() {
+        let contents = "# mine\n[goal]\n# daily = 4\ndaily = 8\nrollover_hour = 4\n";
+        assert_eq!(
+            edit(contents, "goal", "daily", Some("10")).unwrap(),
+            "# mine\n[goal]\n# daily = 4\ndaily = 10\nrollover_hour = 4\n"
+        );
+    }
+
+    #[test]
+    fn edit_removes_a_setting() {
+        let contents = "[goal]\ndaily = 8\nrollover_hour = 4\n";
+        assert_eq!(
+            edit(contents, "goal", "daily", None).unwrap(),
+            "[goal]\nrollover_hour = 4\n"
+        );
+        assert_eq!(edit(contents, "goal", "missing", None), None);
+        assert_eq!(edit(contents, "defaults", "daily", None), None);
+    }
+
+    #[test]
+    fn edit_adds_a_missing_section_at_the_end() {
+        assert_eq!(
+            edit("[goal]\ndaily = 8\n", "defaults", "work", Some("25")).unwrap(),
+            "[goal]\ndaily = 8\n\n[defaults]\nwork = 25\n"
+        );
+        assert_eq!(
+            edit("", "goal", "daily", Some("8")).unwrap(),
+            "[goal]\ndaily = 8\n"
+        );
+    }
+
+    #[test]
+    fn edit_stops_a_section_at_an_array_header() {
+        let contents = "[goal]\ndaily = 8\n[[other]]\ndaily = 1\n";
+        assert_eq!(
+            edit(contents, "goal", "rollover_hour", Some("4")).unwrap(),
+            "[goal]\ndaily = 8\nrollover_hour = 4\n[[other]]\ndaily = 1\n"
+        );
+        assert_eq!(
+            edit(contents, "goal", "daily", None).unwrap(),
+            "[goal]\n[[other]]\ndaily = 1\n"
+        );
+    }
+
+    #[test]
+    fn edit_matches_headers_with_spaces_and_comments() {
+        let contents = "[ budget ] # projects\n\"acme co\" = { limit = \"10h\" }\n";
+        assert_eq!(
+            edit(contents, "budget", &toml_key("acme co"), None).unwrap(),
+            "[ budget ] # projects\n"
+        );
+    }
+
+    #[test]
+    fn update_writes_only_a_valid_config() {
+        let path = temp_config("config-update", "[goal]\ndaily = 8\n");
+        update(&path, "goal", "daily", Some("10")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[goal]\ndaily = 10\n");
+
+        let err = update(&path, "goal", "daily", Some("0")).unwrap_err();
+        assert!(err.ends_with("(not saved)"), "{err}");
+        assert!(err.contains("config.toml:2:9:"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[goal]\ndaily = 10\n");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn line_column_is_one_based() {
+        let text = "ab\ncde\n";
+        assert_eq!(line_column(text, 0), (1, 1));
+        assert_eq!(line_column(text, 1), (1, 2));
+        assert_eq!(line_column(text, 3), (2, 1));
+        assert_eq!(line_column(text, 5), (2, 3));
+        assert_eq!(line_column(text, 100), (3, 1));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_value() {
+        let path = Path::new("config.toml");
+        let err = parse(path, "[defaults]\nwork = \"0m\"\n").unwrap_err();
+        assert_eq!(err, "config.toml:2:8: duration must be > 0");
+        let err = parse(path, "[goal]\ndaily = 8\nrollover_hour = 24\n").unwrap_err();
+        assert_eq!(err, "config.toml:3:17: must be 0 to 23");
+        let err = parse(path, "[defaults]\nwrok = \"25m\"\n").unwrap_err();
+        assert!(
+            err.starts_with("config.toml:2:1: unknown field `wrok`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn lengths_are_strings_or_minutes() {
+        let path = Path::new("config.toml");
+        let config = parse(path, "[defaults]\nwork = 25\nbreak = \"4m30s\"\n").unwrap();
+        assert_eq!(config.defaults.work_secs, Some(25 * 60));
+        assert_eq!(config.defaults.break_secs, Some(270));
+        let err = parse(path, "[defaults]\nwork = true\n").unwrap_err();
+        assert_eq!(
+            err,
+            "config.toml:2:8: expected a length such as \"25m\" or 25"
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! The daemon's control socket.
+//!
+//! Each daemon listens on its own Unix socket (see `control_socket_path`).
+//! A client sends one JSON request per line and gets one JSON response line
+//! back; the connection is then closed, except after `subscribe`, which keeps
+//! it open and sends the timer again every time it changes. The last message a
+//! subscriber gets has no `state`: the daemon is done.
+//!
+//! The daemon sleeps in `poll` on the socket and the phase deadline together,
+//! so it uses no CPU until one of them needs attention. Its end of every
+//! connection is non-blocking: a request is read as it arrives, from the same
+//! `poll`, and a subscriber too slow to take an update is dropped, so no client
+//! can hold up the timer.
+
+use crate::State;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long either side waits for the other: a client for its answer, the
+/// daemon for a request to arrive in full. The daemon answers before running
+/// hooks or notifications, so this only has to cover a busy machine.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest accepted request line.
+const MAX_REQUEST_LEN: u64 = 4096;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub(crate) enum Request {
+    Status,
+    Stop {
+        /// Stopped to make way for a new timer, which the log records separately.
+        #[serde(default)]
+        replaced: bool,
+    },
+    Pause,
+    Resume,
+    Extend {
+        secs: u64,
+    },
+    Shorten {
+        secs: u64,
+    },
+    Skip,
+    /// Acknowledges a phase that is over and waiting, which starts the next one.
+    Next,
+    Subscribe,
+    /// The command a `tock box` ran has exited with `status`, which ends its session.
+    Exited {
+        status: i32,
+    },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Response {
+    /// The timer after the request; `None` once it has stopped.
+    #[serde(default)]
+    pub(crate) state: Option<State>,
+    /// The phase the request ended (`stop`, `skip`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ended: Option<State>,
+    /// Why the request doesn't apply to the timer as it is, e.g. `already paused`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) refused: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+impl Response {
+    pub(crate) fn state(state: &State) -> Response {
+        Response {
+            state: Some(state.clone()),
+            ..Response::default()
+        }
+    }
+
+    pub(crate) fn refused(state: &State, reason: &str) -> Response {
+        Response {
+            refused: Some(reason.to_string()),
+            ..Response::state(state)
+        }
+    }
+
+    pub(crate) fn error(state: &State, error: String) -> Response {
+        Response {
+            error: Some(error),
+            ..Response::state(state)
+        }
+    }
+}
+
+/// Sends one request to the daemon listening at `path` and returns its answer.
+pub(crate) fn request(path: &Path, request: &Request) -> Result<Response, String> {
+    let err = |e: io::Error| format!("failed to reach the timer at {path:?}: {e}");
+    let mut stream = UnixStream::connect(path).map_err(err)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(err)?;
+    stream.set_write_timeout(Some(IO_TIMEOUT)).map_err(err)?;
+    write_line(&mut stream, request).map_err(err)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line).map_err(err)?;
+    if line.is_empty() {
+        return Err(format!("the timer at {path:?} closed the connection"));
+    }
+    serde_json::from_str(&line).map_err(|e| format!("invalid answer from the timer: {e}"))
+}
+
+/// The timer as a daemon publishes it, from `subscribe`.
+pub(crate) struct Subscription {
+    reader: BufReader<UnixStream>,
+}
+
+impl Subscription {
+    /// Waits for the next change. `Ok(None)` means the daemon has gone away.
+    pub(crate) fn next(&mut self) -> Result<Option<Response>, String> {
+        let mut line = String::new();
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .map_err(|e| format!("failed to read from the timer: {e}"))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        serde_json::from_str(&line)
+            .map(Some)
+            .map_err(|e| format!("invalid answer from the timer: {e}"))
+    }
+}
+
+/// Subscribes to the daemon listening at `path`. The first message is the
+/// timer as it is now.
+pub(crate) fn subscribe(path: &Path) -> Result<Subscription, String> {
+    let err = |e: io::Error| format!("failed to reach the timer at {path:?}: {e}");
+    let mut stream = UnixStream::connect(path).map_err(err)?;
+    stream.set_write_timeout(Some(IO_TIMEOUT)).map_err(err)?;
+    write_line(&mut stream, &Request::Subscribe).map_err(err)?;
+    Ok(Subscription {
+        reader: BufReader::new(stream),
+    })
+}
+
+fn write_line<T: Serialize>(stream: &mut UnixStream, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message).map_err(io::Error::other)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+/// A connection whose request is waiting for an answer.
+pub(crate) struct Client {
+    stream: UnixStream,
+}
+
+impl Client {
+    /// Answers the request. A client that has gone away is no concern of the daemon.
+    pub(crate) fn reply(mut self, response: &Response) {
+        let _ = write_line(&mut self.stream, response);
+    }
+}
+
+/// A connection whose request hasn't arrived in full yet.
+struct Incoming {
+    stream: UnixStream,
+    received: Vec<u8>,
+    /// When it is dropped if the request is still incomplete.
+    give_up: Instant,
+}
+
+impl Incoming {
+    /// Reads what has arrived. Returns the request line once it is complete:
+    /// ended by a newline, by the client closing its end, or cut off at
+    /// `MAX_REQUEST_LEN`. `Err` means the connection is of no further use.
+    fn read(&mut self) -> io::Result<Option<String>> {
+        let mut buf = [0; 1024];
+        loop {
+            let room = MAX_REQUEST_LEN as usize - self.received.len();
+            match self.stream.read(&mut buf[..room.min(1024)]) {
+                Ok(0) if self.received.is_empty() => {
+                    return Err(io::ErrorKind::UnexpectedEof.into())
+                }
+                Ok(0) => break,
+                Ok(n) => {
+                    self.received.extend_from_slice(&buf[..n]);
+                    if buf[..n].contains(&b'\n') || self.received.len() >= MAX_REQUEST_LEN as usize
+                    {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        let line = self.received.split(|&b| b == b'\n').next().unwrap_or(&[]);
+        Ok(Some(String::from_utf8_lossy(line).into_owned()))
+    }
+}
+
+pub(crate) struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+    deadline: Deadline,
+    subscribers: Vec<UnixStream>,
+    incoming: Vec<Incoming>,
+}
+
+impl Server {
+    pub(crate) fn bind(path: &Path) -> Result<Server, String> {
+        let dir = path
+            .parent()
+            .ok_or_else(|| format!("invalid socket path {path:?}"))?;
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create socket dir {dir:?}: {e}"))?;
+        // A leftover from an earlier daemon that had the same pid.
+        let _ = std::fs::remove_file(path);
+
+        let err = |e: io::Error| format!("failed to listen on {path:?}: {e}");
+        let listener = UnixListener::bind(path).map_err(err)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).map_err(err)?;
+        listener.set_nonblocking(true).map_err(err)?;
+        Ok(Server {
+            listener,
+            path: path.to_path_buf(),
+            deadline: Deadline::new()?,
+            subscribers: Vec::new(),
+            incoming: Vec::new(),
+        })
+    }
+
+    /// Sleeps until a request comes in or the clock reaches `deadline` (unix
+    /// seconds; `None` waits for requests only). Returns `None` when woken by
+    /// anything but a well-formed request.
+    pub(crate) fn wait(&mut self, deadline: Option<i64>) -> Option<(Client, Request)> {
+        let now = Instant::now();
+        self.incoming.retain(|incoming| incoming.give_up > now);
+        let mut timeout_ms = self.deadline.arm(deadline);
+        if let Some(give_up) = self.incoming.iter().map(|incoming| incoming.give_up).min() {
+            let ms = give_up.saturating_duration_since(now).as_millis() as libc::c_int + 1;
+            timeout_ms = if timeout_ms < 0 {
+                ms
+            } else {
+                timeout_ms.min(ms)
+            };
+        }
+        let mut fds = vec![libc::pollfd {
+            fd: self.listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        if let Some(fd) = self.deadline.fd() {
+            fds.push(libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        let first_subscriber = fds.len();
+        // Subscribers never send anything, so readable means they hung up.
+        fds.extend(self.subscribers.iter().map(|stream| libc::pollfd {
+            fd: stream.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }));
+        let first_incoming = fds.len();
+        fds.extend(self.incoming.iter().map(|incoming| libc::pollfd {
+            fd: incoming.stream.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }));
+
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) };
+        if ready <= 0 {
+            return None;
+        }
+        self.deadline.clear();
+
+        let gone: Vec<bool> = fds[first_subscriber..first_incoming]
+            .iter()
+            .map(|pfd| pfd.revents != 0)
+            .collect();
+        let mut gone = gone.into_iter();
+        self.subscribers.retain(|_| !gone.next().unwrap_or(false));
+
+        let mut ready: Vec<bool> = fds[first_incoming..]
+            .iter()
+            .map(|pfd| pfd.revents != 0)
+            .collect();
+        if fds[0].revents != 0 {
+            self.accept();
+            // A new connection's request has usually arrived with it.
+            ready.resize(self.incoming.len(), true);
+        }
+        // Only one request is handled per call; any other that is complete
+        // wakes the next `poll` straight away.
+        let mut i = 0;
+        let (i, line) = loop {
+            if i == self.incoming.len() {
+                return None;
+            }
+            if !ready[i] {
+                i += 1;
+                continue;
+            }
+            match self.incoming[i].read() {
+                Ok(Some(line)) => break (i, line),
+                Ok(None) => i += 1,
+                Err(_) => {
+                    self.incoming.remove(i);
+                    ready.remove(i);
+                }
+            }
+        };
+        let stream = self.incoming.remove(i).stream;
+        let mut client = Client { stream };
+        match serde_json::from_str(&line) {
+            Ok(request) => Some((client, request)),
+            Err(e) => {
+                let response = Response {
+                    error: Some(format!("invalid request: {e}")),
+                    ..Response::default()
+                };
+                let _ = write_line(&mut client.stream, &response);
+                None
+            }
+        }
+    }
+
+    /// Takes the connections waiting on the socket. Their requests are read
+    /// as they arrive, by later calls to `wait`.
+    fn accept(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.incoming.push(Incoming {
+                    stream,
+                    received: Vec::new(),
+                    give_up: Instant::now() + IO_TIMEOUT,
+                });
+            }
+        }
+    }
+
+    /// Answers a `subscribe` request with `current` and keeps the connection
+    /// open for `publish`.
+    pub(crate) fn subscribe(&mut self, mut client: Client, current: &Response) {
+        if write_line(&mut client.stream, current).is_ok() {
+            self.subscribers.push(client.stream);
+        }
+    }
+
+    /// Sends `response` to every subscriber, dropping the ones that are gone or
+    /// haven't read what they were sent before.
+    pub(crate) fn publish(&mut self, response: &Response) {
+        self.subscribers
+            .retain_mut(|stream| write_line(stream, response).is_ok());
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Wakes `poll` at the phase deadline. On Linux that is a `timerfd` armed for
+/// the absolute wall-clock time, which fires on resume when the deadline passed
+/// during a suspend. Elsewhere it is the `poll` timeout, capped at
+/// `MAX_POLL_WAIT_MS` because that clock may stand still while suspended.
+#[cfg(target_os = "linux")]
+struct Deadline {
+    fd: i32,
+}
+
+#[cfg(target_os = "linux")]
+impl Deadline {
+    fn new() -> Result<Deadline, String> {
+        let fd = unsafe {
+            libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_NONBLOCK | libc::TFD_CLOEXEC)
+        };
+        if fd == -1 {
+            return Err(format!(
+                "failed to create timer: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(Deadline { fd })
+    }
+
+    fn fd(&self) -> Option<i32> {
+        Some(self.fd)
+    }
+
+    /// Arms (or with `None` disarms) the timer. Returns the `poll` timeout.
+    fn arm(&mut self, deadline: Option<i64>) -> libc::c_int {
+        let mut spec: libc::itimerspec = unsafe { std::mem::zeroed() };
+        if let Some(deadline) = deadline {
+            // An all-zero value would disarm the timer instead of firing it.
+            spec.it_value.tv_sec = deadline.max(1) as libc::time_t;
+        }
+        // Setting the clock cancels the timer and wakes `poll`, and the caller
+        // arms it again for the new time.
+        let flags = libc::TFD_TIMER_ABSTIME | libc::TFD_TIMER_CANCEL_ON_SET;
+        unsafe {
+            libc::timerfd_settime(self.fd, flags, &spec, std::ptr::null_mut());
+        }
+        -1
+    }
+
+    fn clear(&mut self) {
+        let mut expirations = 0u64;
+        unsafe {
+            libc::read(self.fd, (&mut expirations as *mut u64).cast(), 8);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Deadline;
+
+/// How late a deadline that passed during a suspend or a clock change may be
+/// noticed where there is no `timerfd`.
+#[cfg(not(target_os = "linux"))]
+const MAX_POLL_WAIT_MS: i64 = 15_000;
+
+#[cfg(not(target_os = "linux"))]
+impl Deadline {
+    fn new() -> Result<Deadline, String> {
+        Ok(Deadline)
+    }
+
+    fn fd(&self) -> Option<i32> {
+        None
+    }
+
+    fn arm(&mut self, deadline: Option<i64>) -> libc::c_int {
+        let Some(deadline) = deadline else {
+            return -1;
+        };
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        (deadline * 1000 - now_ms).clamp(0, MAX_POLL_WAIT_MS) as libc::c_int
+    }
+
+    fn clear(&mut self) {}
+}
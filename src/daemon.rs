@@ -0,0 +1,698 @@
+//! The background process that runs a timer.
+//!
+//! The daemon owns its timer: every change arrives as a request on its control
+//! socket, and state.json is only the snapshot it leaves for other readers
+//! (`tock prompt`, `tock bar`, a later `tock` run looking for the daemon).
+//! Notifications and hooks run on a worker thread, in order, so a slow hook
+//! never holds up a reply or the next phase. Changes the daemon makes on its
+//! own or for `skip`, `pause` and the like are made holding the `StateLock`.
+
+use crate::budget;
+use crate::control::{Client, Request, Response, Server};
+use crate::hooks::Hook;
+use crate::identity::process_exists;
+use crate::lock::StateLock;
+use crate::log::{EndReason, LogEntry};
+use crate::notify::Notification;
+use crate::{
+    clear_state, log_entry, notify, now_unix, run_hook, write_state, Mode, Pause, State,
+    MAX_MINUTES,
+};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long a request waits for another command to release the `StateLock`.
+const LOCK_WAIT: Duration = Duration::from_secs(1);
+/// How often a session past its end checks that its `tock box` is still there.
+const BOX_CHECK_SECS: i64 = 5;
+
+/// Runs `current` and the phases after it until the sequence ends or a client stops it.
+/// `ready_fd` is written to once the daemon can take requests.
+pub(crate) fn run(
+    mut current: State,
+    state_path: &Path,
+    socket_path: &Path,
+    ready_fd: Option<i32>,
+    tty: Option<PathBuf>,
+) -> Result<(), String> {
+    let mut server = Server::bind(socket_path)?;
+    // No `StateLock` here: the `tock start` waiting for `ready_fd` holds it.
+    write_state(state_path, &current)?;
+    if let Some(fd) = ready_fd {
+        signal_ready(fd);
+    }
+
+    let announcer = Announcer::spawn(tty);
+    // A scheduled phase gets its hook when it begins.
+    if !current.scheduled {
+        let first = current.clone();
+        announcer.send(move |_| run_hook(Hook::SessionStart, &first, None));
+    }
+
+    let result = run_loop(&mut current, state_path, &mut server, &announcer);
+    server.publish(&Response::default());
+    drop(server);
+    announcer.finish();
+    result
+}
+
+fn run_loop(
+    current: &mut State,
+    state_path: &Path,
+    server: &mut Server,
+    announcer: &Announcer,
+) -> Result<(), String> {
+    let mut suspends = SuspendWatch::new();
+    loop {
+        let now = now_unix();
+        suspends.update(now);
+        let due = current.deadline().is_some_and(|deadline| deadline <= now);
+        // A `tock box` ends its session once the command has exited; the
+        // session only ends here if the box itself went away.
+        let boxed = due && !current.scheduled && current.box_pid.is_some_and(process_exists);
+        if due && !boxed {
+            if let Some(_lock) = StateLock::try_acquire(state_path)? {
+                if current.scheduled {
+                    begin(current, now, true, state_path, announcer)?;
+                    server.publish(&Response::state(current));
+                    continue;
+                }
+                let while_suspended = suspends.slept_through(current.end_ts);
+                if await_ack(current, while_suspended, state_path, announcer)? {
+                    server.publish(&Response::state(current));
+                    continue;
+                }
+                let ending = Ending {
+                    reason: EndReason::Completed,
+                    while_suspended,
+                };
+                let (_, done) = end_phase(current, ending, now, state_path, announcer)?;
+                if done {
+                    return Ok(());
+                }
+                server.publish(&Response::state(current));
+                continue;
+            }
+        }
+
+        // Whoever holds the lock may be waiting on a `stop` request, so keep
+        // answering and try again a second later.
+        let deadline = if boxed {
+            Some(now + BOX_CHECK_SECS)
+        } else if due {
+            Some(now + 1)
+        } else {
+            current.deadline()
+        };
+        let Some((client, request)) = server.wait(deadline) else {
+            continue;
+        };
+        let _lock = match request {
+            Request::Status | Request::Subscribe | Request::Stop { .. } => None,
+            _ => match StateLock::acquire_within(state_path, LOCK_WAIT)? {
+                Some(lock) => Some(lock),
+                None => {
+                    let busy = "the timer is busy with another tock command; try again";
+                    client.reply(&Response::error(current, busy.to_string()));
+                    continue;
+                }
+            },
+        };
+        match request {
+            Request::Status => client.reply(&Response::state(current)),
+            Request::Subscribe => server.subscribe(client, &Response::state(current)),
+            // Sent by a command that holds the lock for us.
+            Request::Stop { replaced } => {
+                let reason = if replaced {
+                    EndReason::ReplacedByNewTimer
+                } else {
+                    EndReason::Stopped
+                };
+                stop(current, reason, None, state_path, client, announcer);
+                return Ok(());
+            }
+            // The box ran its course if its time was up, or was cut short.
+            Request::Exited { status } => {
+                let reason = if current.paused.is_none() && now_unix() >= current.end_ts {
+                    EndReason::Completed
+                } else {
+                    EndReason::Stopped
+                };
+                stop(current, reason, Some(status), state_path, client, announcer);
+                return Ok(());
+            }
+            // Skipping the wait: the phase begins now, with nothing to announce.
+            Request::Skip if current.scheduled => {
+                match begin(current, now_unix(), false, state_path, announcer) {
+                    Ok(()) => client.reply(&Response::state(current)),
+                    Err(e) => client.reply(&Response::error(current, e)),
+                }
+                server.publish(&Response::state(current));
+            }
+            Request::Next if !current.awaiting_ack => {
+                let reason = if current.scheduled {
+                    "not started yet (`tock skip` starts it now)"
+                } else {
+                    "the phase isn't over yet (`tock skip` ends it now)"
+                };
+                client.reply(&Response::refused(current, reason));
+            }
+            // A phase waiting for `tock next` ran its course; skipping it is acknowledging it.
+            Request::Skip | Request::Next => {
+                let reason = if current.awaiting_ack {
+                    EndReason::Completed
+                } else {
+                    EndReason::Skipped
+                };
+                let ending = Ending {
+                    reason,
+                    while_suspended: false,
+                };
+                let (ended, done) = end_phase(current, ending, now_unix(), state_path, announcer)?;
+                client.reply(&Response {
+                    state: (!done).then(|| current.clone()),
+                    ended: Some(ended),
+                    ..Response::default()
+                });
+                if done {
+                    return Ok(());
+                }
+                server.publish(&Response::state(current));
+            }
+            Request::Pause | Request::Resume | Request::Extend { .. } | Request::Shorten { .. } => {
+                let hook = match request {
+                    Request::Pause => Some(Hook::Paused),
+                    Request::Resume => Some(Hook::Resumed),
+                    _ => None,
+                };
+                match adjust(current, &request, now_unix()) {
+                    Ok(()) => {
+                        let written = write_state(state_path, current);
+                        match written {
+                            Ok(()) => client.reply(&Response::state(current)),
+                            Err(e) => client.reply(&Response::error(current, e)),
+                        }
+                        server.publish(&Response::state(current));
+                        if let Some(hook) = hook {
+                            let state = current.clone();
+                            announcer.send(move |_| run_hook(hook, &state, None));
+                        }
+                    }
+                    Err(Refusal::Refused(reason)) => {
+                        client.reply(&Response::refused(current, reason))
+                    }
+                    Err(Refusal::Invalid(e)) => client.reply(&Response::error(current, e)),
+                }
+            }
+        }
+    }
+}
+
+enum Refusal {
+    /// The request doesn't apply to the timer as it is.
+    Refused(&'static str),
+    Invalid(String),
+}
+
+/// Applies a pause, resume, extend or shorten request to the running phase.
+fn adjust(state: &mut State, request: &Request, now: i64) -> Result<(), Refusal> {
+    if state.scheduled {
+        return Err(Refusal::Refused(
+            "not started yet (`tock skip` starts it now)",
+        ));
+    }
+    if state.awaiting_ack {
+        return Err(Refusal::Refused(
+            "the phase is over (`tock next` starts the next one)",
+        ));
+    }
+    let too_long = || Refusal::Invalid(format!("phase too long (max {MAX_MINUTES} minutes)"));
+    match (request, &mut state.paused) {
+        (Request::Pause, Some(_)) => return Err(Refusal::Refused("already paused")),
+        (Request::Pause, None) => {
+            state.paused = Some(Pause {
+                since: now,
+                remaining_secs: state.remaining_secs(now),
+            });
+        }
+        (Request::Resume, None) => return Err(Refusal::Refused("not paused")),
+        (Request::Resume, Some(pause)) => {
+            let remaining_secs = pause.remaining_secs;
+            state.paused_secs = state.paused_secs_at(now);
+            state.end_ts = now
+                .checked_add(remaining_secs as i64)
+                .ok_or_else(|| Refusal::Invalid("timestamp overflow".to_string()))?;
+            state.paused = None;
+        }
+        (Request::Extend { secs }, Some(pause)) => {
+            pause.remaining_secs = pause
+                .remaining_secs
+                .checked_add(*secs)
+                .filter(|&remaining| remaining <= MAX_MINUTES * 60)
+                .ok_or_else(too_long)?;
+        }
+        (Request::Extend { secs }, None) => {
+            state.end_ts = i64::try_from(*secs)
+                .ok()
+                .and_then(|secs| state.end_ts.checked_add(secs))
+                .filter(|&end_ts| end_ts - now <= (MAX_MINUTES * 60) as i64)
+                .ok_or_else(too_long)?;
+        }
+        (Request::Shorten { secs }, Some(pause)) => {
+            pause.remaining_secs = pause.remaining_secs.saturating_sub(*secs);
+        }
+        (Request::Shorten { secs }, None) => {
+            let secs = i64::try_from(*secs).unwrap_or(i64::MAX);
+            state.end_ts = state.end_ts.saturating_sub(secs).max(now);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Holds a phase that ran out in a `--manual-advance` sequence until `tock
+/// next`, and says it is waiting. Returns `false` if the phase should end
+/// now: the sequence advances by itself, or this was its last step.
+fn await_ack(
+    current: &mut State,
+    while_suspended: bool,
+    state_path: &Path,
+    announcer: &Announcer,
+) -> Result<bool, String> {
+    let next = current
+        .sequence
+        .as_ref()
+        .filter(|sequence| sequence.manual_advance())
+        .and_then(|sequence| sequence.next());
+    let Some(next) = next else {
+        return Ok(false);
+    };
+    current.awaiting_ack = true;
+    write_state(state_path, current)?;
+
+    let finished = current.mode;
+    announcer.send(move |tty| {
+        notify(
+            &Notification::awaiting_ack(finished, next.mode, while_suspended),
+            tty,
+        );
+    });
+    Ok(true)
+}
+
+/// Why and how a phase is ending.
+#[derive(Copy, Clone)]
+struct Ending {
+    reason: EndReason,
+    /// The deadline passed while the machine was suspended.
+    while_suspended: bool,
+}
+
+/// Ends the current phase and starts the next one in the sequence. Returns the
+/// phase that ended, and `true` once there is no next phase and the daemon is
+/// done.
+fn end_phase(
+    current: &mut State,
+    ending: Ending,
+    now: i64,
+    state_path: &Path,
+    announcer: &Announcer,
+) -> Result<(State, bool), String> {
+    let Ending {
+        reason,
+        while_suspended,
+    } = ending;
+    // A phase that ran out during a suspend ended at its deadline, not when
+    // the machine woke up; the next one can only start now, though.
+    let end_ts = if while_suspended {
+        current.end_ts.min(now)
+    } else {
+        now
+    };
+    // Taken before `end_ts` moves, so the wait for `tock next` counts as overtime.
+    let mut entry = LogEntry::from_state(current, end_ts, reason);
+    entry.while_suspended = while_suspended;
+    let mut ended = current.clone();
+    ended.paused_secs = ended.paused_secs_at(end_ts);
+    ended.paused = None;
+    ended.end_ts = end_ts;
+    log_entry(&entry);
+    let check_budgets = reason == EndReason::Completed && ended.mode == Mode::Work;
+
+    let next = ended
+        .sequence
+        .as_ref()
+        .and_then(|sequence| sequence.advance())
+        .map(|sequence| {
+            let step = sequence.current();
+            let end_ts = now
+                .checked_add(step.secs as i64)
+                .ok_or_else(|| "timestamp overflow".to_string())?;
+            Ok::<_, String>(State {
+                pid: ended.pid,
+                mode: step.mode,
+                start_ts: now,
+                end_ts,
+                secs: step.secs,
+                sequence: Some(sequence),
+                meta: ended.meta.clone(),
+                paused: None,
+                paused_secs: 0,
+                scheduled: false,
+                awaiting_ack: false,
+                box_pid: None,
+                identity: ended.identity.clone(),
+            })
+        })
+        .transpose()?;
+
+    match &next {
+        Some(next) => {
+            *current = next.clone();
+            write_state(state_path, current)?;
+        }
+        None => {
+            let _ = clear_state(state_path);
+        }
+    }
+
+    // A skipped phase was ended on purpose, and one that waited for `tock next`
+    // was announced when it ran out, so there is nothing to announce.
+    let announce = reason != EndReason::Skipped && !ended.awaiting_ack;
+    let done = next.is_none();
+    let result = ended.clone();
+    announcer.send(move |tty| {
+        if announce {
+            let next = next.as_ref().map(|next| next.mode);
+            notify(
+                &Notification::phase_finished(ended.mode, next, while_suspended),
+                tty,
+            );
+        }
+        if check_budgets {
+            notify_budgets(&entry, tty);
+        }
+        run_hook(Hook::SessionEnd, &ended, Some(reason));
+        match &next {
+            Some(next) => {
+                run_hook(Hook::PhaseChange, next, None);
+                run_hook(Hook::SessionStart, next, None);
+            }
+            None if ended.sequence.is_some() => run_hook(Hook::CycleComplete, &ended, Some(reason)),
+            None => {}
+        }
+    });
+    Ok((result, done))
+}
+
+/// Begins a scheduled phase. It runs for its full length from `now`, however
+/// late that is.
+fn begin(
+    current: &mut State,
+    now: i64,
+    announce: bool,
+    state_path: &Path,
+    announcer: &Announcer,
+) -> Result<(), String> {
+    let end_ts = now
+        .checked_add(current.secs as i64)
+        .ok_or_else(|| "timestamp overflow".to_string())?;
+    current.scheduled = false;
+    current.start_ts = now;
+    current.end_ts = end_ts;
+    write_state(state_path, current)?;
+
+    let state = current.clone();
+    announcer.send(move |tty| {
+        if announce {
+            notify(&Notification::phase_started(state.mode), tty);
+        }
+        run_hook(Hook::SessionStart, &state, None);
+    });
+    Ok(())
+}
+
+/// Ends the timer. A scheduled phase that never began is simply called off:
+/// there is nothing to log and no hooks run. `exit_status` is how the command
+/// of a `tock box` exited.
+fn stop(
+    current: &State,
+    reason: EndReason,
+    exit_status: Option<i32>,
+    state_path: &Path,
+    client: Client,
+    announcer: &Announcer,
+) {
+    let now = now_unix();
+    let mut entry = LogEntry::from_state(current, now, reason);
+    entry.exit_status = exit_status;
+    if !current.scheduled {
+        log_entry(&entry);
+    }
+    let cleared = clear_state(state_path);
+
+    let mut ended = current.clone();
+    ended.paused_secs = ended.paused_secs_at(now);
+    client.reply(&Response {
+        ended: Some(ended),
+        error: cleared.err(),
+        ..Response::default()
+    });
+
+    if current.scheduled {
+        return;
+    }
+    // The command of a `tock box` ending is its session ending, however early.
+    let check_budgets = exit_status.is_some() && current.mode == Mode::Work;
+    let state = current.clone();
+    announcer.send(move |tty| {
+        if check_budgets {
+            notify_budgets(&entry, tty);
+        }
+        run_hook(Hook::SessionEnd, &state, Some(reason));
+        if reason == EndReason::Stopped {
+            run_hook(Hook::Stopped, &state, Some(reason));
+        }
+    });
+}
+
+/// Sends the notifications for budgets that `entry`, just logged, took past a threshold.
+fn notify_budgets(entry: &LogEntry, tty: Option<&Path>) {
+    match budget::notifications(entry) {
+        Ok(notifications) => notifications.iter().for_each(|n| notify(n, tty)),
+        Err(e) => eprintln!("warning: {e}"),
+    }
+}
+
+/// Notices the machine being suspended, to tell a phase that ran out during a
+/// suspend from one that merely finished.
+struct SuspendWatch {
+    /// `time_suspended` when last looked at.
+    total: Option<Duration>,
+    /// Wall-clock span of the latest suspend seen, in unix seconds.
+    latest: Option<(i64, i64)>,
+}
+
+impl SuspendWatch {
+    fn new() -> SuspendWatch {
+        SuspendWatch {
+            total: time_suspended(),
+            latest: None,
+        }
+    }
+
+    /// Called whenever the daemon wakes up. A suspend ended just before, since
+    /// an expired deadline wakes the daemon as soon as the machine resumes.
+    fn update(&mut self, now: i64) {
+        let total = time_suspended();
+        if let (Some(before), Some(after)) = (self.total, total) {
+            let slept = after.saturating_sub(before);
+            if slept >= Duration::from_secs(1) {
+                self.latest = Some((now - slept.as_secs() as i64, now));
+            }
+        }
+        self.total = total;
+    }
+
+    fn slept_through(&self, deadline: i64) -> bool {
+        self.latest
+            .is_some_and(|(from, to)| from <= deadline && deadline <= to)
+    }
+}
+
+/// How long the machine has been suspended since boot: the difference between
+/// a clock that keeps counting during suspend and one that doesn't. `None`
+/// where there are no such clocks.
+fn time_suspended() -> Option<Duration> {
+    #[cfg(target_os = "linux")]
+    let clocks = Some((libc::CLOCK_BOOTTIME, libc::CLOCK_MONOTONIC));
+    #[cfg(target_os = "macos")]
+    let clocks = Some((libc::CLOCK_MONOTONIC, libc::CLOCK_UPTIME_RAW));
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    let clocks: Option<(libc::clockid_t, libc::clockid_t)> = None;
+
+    let (with_suspend, without_suspend) = clocks?;
+    clock(with_suspend)?.checked_sub(clock(without_suspend)?)
+}
+
+fn clock(id: libc::clockid_t) -> Option<Duration> {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    if unsafe { libc::clock_gettime(id, &mut ts) } == -1 {
+        return None;
+    }
+    Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+fn signal_ready(fd: i32) {
+    let byte = 1u8;
+    unsafe {
+        libc::write(fd, (&byte as *const u8).cast(), 1);
+        libc::close(fd);
+    }
+}
+
+type Job = Box<dyn FnOnce(Option<&Path>) + Send>;
+
+/// Runs notifications and hooks one after another on a thread of their own.
+struct Announcer {
+    sender: mpsc::Sender<Job>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Announcer {
+    /// `tty` is the terminal the timer was started from, for the bell notifier.
+    fn spawn(tty: Option<PathBuf>) -> Announcer {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let thread = thread::spawn(move || {
+            for job in receiver {
+                job(tty.as_deref());
+            }
+        });
+        Announcer { sender, thread }
+    }
+
+    fn send(&self, job: impl FnOnce(Option<&Path>) + Send + 'static) {
+        let _ = self.sender.send(Box::new(job));
+    }
+
+    /// Waits for everything already sent to finish.
+    fn finish(self) {
+        drop(self.sender);
+        let _ = self.thread.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::running;
+
+    fn apply(state: &mut State, request: Request, now: i64) -> Result<(), String> {
+        adjust(state, &request, now).map_err(|refusal| match refusal {
+            Refusal::Refused(reason) => reason.to_string(),
+            Refusal::Invalid(e) => e,
+        })
+    }
+
+    #[test]
+    fn pausing_moves_the_end_by_the_paused_span() {
+        let mut state = running(Mode::Work, 1000, 1500);
+        apply(&mut state, Request::Pause, 1100).unwrap();
+        assert_eq!(
+            state.paused,
+            Some(Pause {
+                since: 1100,
+                remaining_secs: 1400
+            })
+        );
+        assert_eq!(state.remaining_secs(1400), 1400);
+        assert_eq!(state.deadline(), None);
+        assert_eq!(state.paused_secs_at(1400), 300);
+
+        apply(&mut state, Request::Resume, 1400).unwrap();
+        assert_eq!(state.paused, None);
+        assert_eq!(state.end_ts, 2500 + 300);
+        assert_eq!(state.paused_secs, 300);
+        assert_eq!(state.remaining_secs(1400), 1400);
+
+        apply(&mut state, Request::Pause, 2000).unwrap();
+        apply(&mut state, Request::Resume, 2060).unwrap();
+        assert_eq!(state.end_ts, 2500 + 360);
+        assert_eq!(state.paused_secs, 360);
+    }
+
+    #[test]
+    fn pausing_twice_or_resuming_a_running_phase_is_refused() {
+        let mut state = running(Mode::Work, 1000, 1500);
+        assert_eq!(
+            apply(&mut state, Request::Resume, 1100),
+            Err("not paused".to_string())
+        );
+        apply(&mut state, Request::Pause, 1100).unwrap();
+        assert_eq!(
+            apply(&mut state, Request::Pause, 1200),
+            Err("already paused".to_string())
+        );
+        assert_eq!(state.paused.map(|pause| pause.since), Some(1100));
+    }
+
+    #[test]
+    fn paused_time_goes_into_the_log() {
+        let mut state = running(Mode::Work, 1000, 1500);
+        apply(&mut state, Request::Pause, 1100).unwrap();
+        apply(&mut state, Request::Resume, 1400).unwrap();
+        apply(&mut state, Request::Pause, 1500).unwrap();
+        // Stopped while paused: the pause in progress counts too.
+        let entry = LogEntry::from_state(&state, 1600, EndReason::Stopped);
+        assert_eq!(entry.paused_secs, 400);
+        assert_eq!(entry.active_secs(), 200);
+        // Spread evenly over the session when it is split.
+        assert_eq!(entry.active_secs_within(1000, 1300), 100);
+    }
+
+    #[test]
+    fn extending_is_capped_at_the_longest_phase() {
+        let max_secs = MAX_MINUTES * 60;
+        let mut state = running(Mode::Work, 1000, 1500);
+        apply(&mut state, Request::Extend { secs: 600 }, 1000).unwrap();
+        assert_eq!(state.end_ts, 3100);
+        let too_long = Err(format!("phase too long (max {MAX_MINUTES} minutes)"));
+        let secs = max_secs - 2100 + 1;
+        assert_eq!(apply(&mut state, Request::Extend { secs }, 1000), too_long);
+        assert_eq!(state.end_ts, 3100);
+        apply(&mut state, Request::Extend { secs: secs - 1 }, 1000).unwrap();
+        assert_eq!(state.remaining_secs(1000), max_secs);
+
+        apply(&mut state, Request::Pause, 1000).unwrap();
+        assert_eq!(
+            apply(&mut state, Request::Extend { secs: 1 }, 1000),
+            too_long
+        );
+        let mut state = running(Mode::Work, 1000, 1500);
+        assert_eq!(
+            apply(&mut state, Request::Extend { secs: u64::MAX }, 1000),
+            too_long
+        );
+    }
+
+    #[test]
+    fn shortening_past_now_ends_the_phase() {
+        let mut state = running(Mode::Work, 1000, 1500);
+        apply(&mut state, Request::Shorten { secs: 600 }, 1200).unwrap();
+        assert_eq!(state.end_ts, 1900);
+        apply(&mut state, Request::Shorten { secs: 3600 }, 1200).unwrap();
+        assert_eq!(state.end_ts, 1200);
+        assert_eq!(state.remaining_secs(1200), 0);
+        assert_eq!(state.deadline(), Some(1200));
+        let mut state = running(Mode::Work, 1000, 1500);
+        apply(&mut state, Request::Shorten { secs: u64::MAX }, 1200).unwrap();
+        assert_eq!(state.end_ts, 1200);
+
+        let mut state = running(Mode::Work, 1000, 1500);
+        apply(&mut state, Request::Pause, 1200).unwrap();
+        apply(&mut state, Request::Shorten { secs: 3600 }, 1200).unwrap();
+        assert_eq!(state.remaining_secs(1300), 0);
+    }
+}
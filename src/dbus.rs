@@ -0,0 +1,807 @@
+//! Just enough of the D-Bus wire protocol to call methods on the session bus,
+//! plus a client for `org.freedesktop.Notifications`.
+//!
+//! Only what tock needs is implemented: EXTERNAL authentication over a Unix
+//! socket, method calls and their replies, and the basic types used by the
+//! notification interface.
+
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Far below the protocol limit, but nothing tock talks to sends more.
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+const BUS_NAME: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    Byte(u8),
+    Bool(bool),
+    I32(i32),
+    U32(u32),
+    Str(String),
+    ObjectPath(String),
+    Signature(String),
+    /// Element signature and elements; the signature is kept so empty arrays
+    /// can still be marshalled.
+    Array(String, Vec<Value>),
+    Struct(Vec<Value>),
+    DictEntry(Box<Value>, Box<Value>),
+    Variant(Box<Value>),
+}
+
+impl Value {
+    pub(crate) fn signature(&self) -> String {
+        match self {
+            Value::Byte(_) => "y".to_string(),
+            Value::Bool(_) => "b".to_string(),
+            Value::I32(_) => "i".to_string(),
+            Value::U32(_) => "u".to_string(),
+            Value::Str(_) => "s".to_string(),
+            Value::ObjectPath(_) => "o".to_string(),
+            Value::Signature(_) => "g".to_string(),
+            Value::Array(elem, _) => format!("a{elem}"),
+            Value::Struct(fields) => {
+                let inner: String = fields.iter().map(Value::signature).collect();
+                format!("({inner})")
+            }
+            Value::DictEntry(key, value) => {
+                format!("{{{0}{1}}}", key.signature(), value.signature())
+            }
+            Value::Variant(_) => "v".to_string(),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) | Value::ObjectPath(s) | Value::Signature(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_u32(&self) -> Option<u32> {
+        match self {
+            Value::U32(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// Builds an `a{sv}` dictionary, the usual shape for options and hints.
+pub(crate) fn string_variant_dict(entries: Vec<(&str, Value)>) -> Value {
+    let entries = entries
+        .into_iter()
+        .map(|(key, value)| {
+            Value::DictEntry(
+                Box::new(Value::Str(key.to_string())),
+                Box::new(Value::Variant(Box::new(value))),
+            )
+        })
+        .collect();
+    Value::Array("{sv}".to_string(), entries)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum MessageType {
+    MethodCall = 1,
+    MethodReturn = 2,
+    Error = 3,
+    Signal = 4,
+}
+
+/// A message with its header fields decoded. The body is kept raw and only
+/// decoded on request, so unrelated messages with types we don't support
+/// (signals, mostly) never cause errors.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Message {
+    pub(crate) kind: Option<MessageType>,
+    pub(crate) flags: u8,
+    /// Read by the stub notification server in tests/dbus_notify.rs to reply.
+    #[allow(dead_code)]
+    pub(crate) serial: u32,
+    pub(crate) path: Option<String>,
+    pub(crate) interface: Option<String>,
+    pub(crate) member: Option<String>,
+    pub(crate) error_name: Option<String>,
+    pub(crate) reply_serial: Option<u32>,
+    pub(crate) destination: Option<String>,
+    /// Read by the stub notification server in tests/dbus_notify.rs to reply.
+    #[allow(dead_code)]
+    pub(crate) sender: Option<String>,
+    pub(crate) signature: String,
+    big_endian: bool,
+    body_offset: usize,
+    raw: Vec<u8>,
+}
+
+impl Message {
+    pub(crate) fn new(kind: MessageType) -> Message {
+        Message {
+            kind: Some(kind),
+            ..Message::default()
+        }
+    }
+
+    pub(crate) fn method_call(
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+    ) -> Message {
+        Message {
+            path: Some(path.to_string()),
+            interface: Some(interface.to_string()),
+            member: Some(member.to_string()),
+            destination: Some(destination.to_string()),
+            ..Message::new(MessageType::MethodCall)
+        }
+    }
+
+    /// Decodes the body according to the message signature.
+    pub(crate) fn body(&self) -> Result<Vec<Value>, String> {
+        let mut reader = Reader {
+            buf: &self.raw,
+            pos: self.body_offset,
+            big_endian: self.big_endian,
+        };
+        let mut values = Vec::new();
+        let mut rest = self.signature.as_str();
+        while !rest.is_empty() {
+            let (ty, tail) = split_first_type(rest)?;
+            values.push(reader.read_value(ty)?);
+            rest = tail;
+        }
+        Ok(values)
+    }
+
+    fn encode(&self, serial: u32, body: &[Value]) -> Result<Vec<u8>, String> {
+        let kind = self
+            .kind
+            .ok_or_else(|| "message type not set".to_string())?;
+
+        let mut body_writer = Writer::default();
+        let mut signature = String::new();
+        for value in body {
+            body_writer.put_value(value);
+            signature.push_str(&value.signature());
+        }
+
+        let mut fields = Vec::new();
+        let mut field = |code: u8, value: Value| {
+            fields.push(Value::Struct(vec![
+                Value::Byte(code),
+                Value::Variant(Box::new(value)),
+            ]));
+        };
+        if let Some(path) = &self.path {
+            field(1, Value::ObjectPath(path.clone()));
+        }
+        if let Some(interface) = &self.interface {
+            field(2, Value::Str(interface.clone()));
+        }
+        if let Some(member) = &self.member {
+            field(3, Value::Str(member.clone()));
+        }
+        if let Some(error_name) = &self.error_name {
+            field(4, Value::Str(error_name.clone()));
+        }
+        if let Some(reply_serial) = self.reply_serial {
+            field(5, Value::U32(reply_serial));
+        }
+        if let Some(destination) = &self.destination {
+            field(6, Value::Str(destination.clone()));
+        }
+        if !signature.is_empty() {
+            field(8, Value::Signature(signature));
+        }
+
+        let mut writer = Writer::default();
+        writer.put_u8(b'l');
+        writer.put_u8(kind as u8);
+        writer.put_u8(self.flags);
+        writer.put_u8(1);
+        writer.put_u32(body_writer.buf.len() as u32);
+        writer.put_u32(serial);
+        writer.put_value(&Value::Array("(yv)".to_string(), fields));
+        writer.align(8);
+        writer.buf.extend_from_slice(&body_writer.buf);
+        Ok(writer.buf)
+    }
+
+    fn decode(raw: Vec<u8>) -> Result<Message, String> {
+        let big_endian = match raw.first() {
+            Some(b'l') => false,
+            Some(b'B') => true,
+            _ => return Err("invalid D-Bus message: bad endianness marker".to_string()),
+        };
+        let kind = match raw[1] {
+            1 => Some(MessageType::MethodCall),
+            2 => Some(MessageType::MethodReturn),
+            3 => Some(MessageType::Error),
+            4 => Some(MessageType::Signal),
+            _ => None,
+        };
+
+        let mut reader = Reader {
+            buf: &raw,
+            pos: 8,
+            big_endian,
+        };
+        let serial = reader.get_u32()?;
+        let Value::Array(_, fields) = reader.read_value("a(yv)")? else {
+            return Err("invalid D-Bus message: bad header fields".to_string());
+        };
+        reader.align(8)?;
+        let body_offset = reader.pos;
+
+        let mut message = Message {
+            kind,
+            flags: raw[2],
+            serial,
+            big_endian,
+            body_offset,
+            ..Message::default()
+        };
+        for field in fields {
+            let Value::Struct(parts) = field else {
+                continue;
+            };
+            let (Some(Value::Byte(code)), Some(Value::Variant(value))) =
+                (parts.first(), parts.get(1))
+            else {
+                continue;
+            };
+            let text = value.as_str().map(str::to_string);
+            match code {
+                1 => message.path = text,
+                2 => message.interface = text,
+                3 => message.member = text,
+                4 => message.error_name = text,
+                5 => message.reply_serial = value.as_u32(),
+                6 => message.destination = text,
+                7 => message.sender = text,
+                8 => message.signature = text.unwrap_or_default(),
+                _ => {}
+            }
+        }
+        message.raw = raw;
+        Ok(message)
+    }
+}
+
+pub(crate) struct Connection {
+    stream: UnixStream,
+    next_serial: u32,
+}
+
+impl Connection {
+    /// Connects to the session bus named by `DBUS_SESSION_BUS_ADDRESS`, falling
+    /// back to the systemd default of `$XDG_RUNTIME_DIR/bus`.
+    pub(crate) fn session() -> Result<Connection, String> {
+        let address = match std::env::var("DBUS_SESSION_BUS_ADDRESS") {
+            Ok(address) if !address.is_empty() => address,
+            _ => match std::env::var("XDG_RUNTIME_DIR") {
+                Ok(dir) if !dir.is_empty() => format!("unix:path={dir}/bus"),
+                _ => return Err("no session bus: DBUS_SESSION_BUS_ADDRESS is not set".to_string()),
+            },
+        };
+        Connection::open(&address)
+    }
+
+    /// Connects to the first reachable address in a D-Bus address list,
+    /// authenticates and registers with the bus.
+    pub(crate) fn open(address: &str) -> Result<Connection, String> {
+        let mut last_err = format!("no usable address in {address:?}");
+        for entry in address.split(';').filter(|entry| !entry.is_empty()) {
+            match connect_unix(entry) {
+                Ok(stream) => {
+                    let mut conn = Connection {
+                        stream,
+                        next_serial: 1,
+                    };
+                    conn.authenticate()?;
+                    conn.hello()?;
+                    return Ok(conn);
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn authenticate(&mut self) -> Result<(), String> {
+        let uid = unsafe { libc::geteuid() }.to_string();
+        let hex_uid: String = uid.bytes().map(|b| format!("{b:02x}")).collect();
+        self.write_raw(format!("\0AUTH EXTERNAL {hex_uid}\r\n").as_bytes())?;
+        let reply = self.read_auth_line()?;
+        if !reply.starts_with("OK ") {
+            return Err(format!("D-Bus authentication rejected: {reply}"));
+        }
+        self.write_raw(b"BEGIN\r\n")
+    }
+
+    /// The bus drops connections that don't say Hello first.
+    fn hello(&mut self) -> Result<(), String> {
+        let reply = self.call(
+            Message::method_call(BUS_NAME, BUS_PATH, BUS_NAME, "Hello"),
+            &[],
+        )?;
+        match reply.first().and_then(Value::as_str) {
+            Some(_) => Ok(()),
+            None => Err("unexpected reply to Hello".to_string()),
+        }
+    }
+
+    /// Sends a method call and waits for its reply, skipping unrelated
+    /// messages such as signals. D-Bus errors become `Err("name: message")`.
+    pub(crate) fn call(&mut self, message: Message, body: &[Value]) -> Result<Vec<Value>, String> {
+        let serial = self.send(&message, body)?;
+        loop {
+            let reply = self.read_message()?;
+            if reply.reply_serial != Some(serial) {
+                continue;
+            }
+            return match reply.kind {
+                Some(MessageType::Error) => {
+                    let name = reply.error_name.clone().unwrap_or_default();
+                    let detail = reply
+                        .body()
+                        .ok()
+                        .and_then(|body| body.first().and_then(|v| v.as_str().map(str::to_string)))
+                        .unwrap_or_default();
+                    Err(format!("{name}: {detail}"))
+                }
+                _ => reply.body(),
+            };
+        }
+    }
+
+    /// Sends a message and returns the serial it was sent with.
+    pub(crate) fn send(&mut self, message: &Message, body: &[Value]) -> Result<u32, String> {
+        let serial = self.next_serial;
+        self.next_serial = self.next_serial.wrapping_add(1).max(1);
+        let bytes = message.encode(serial, body)?;
+        self.write_raw(&bytes)?;
+        Ok(serial)
+    }
+
+    pub(crate) fn read_message(&mut self) -> Result<Message, String> {
+        let mut fixed = [0u8; 16];
+        self.read_exact(&mut fixed)?;
+        let read_u32 = |bytes: [u8; 4]| match fixed[0] {
+            b'B' => u32::from_be_bytes(bytes),
+            _ => u32::from_le_bytes(bytes),
+        };
+        let body_len = read_u32([fixed[4], fixed[5], fixed[6], fixed[7]]) as usize;
+        let fields_len = read_u32([fixed[12], fixed[13], fixed[14], fixed[15]]) as usize;
+        let header_len = (16 + fields_len).next_multiple_of(8);
+        let total = header_len
+            .checked_add(body_len)
+            .filter(|&total| total <= MAX_MESSAGE_LEN)
+            .ok_or_else(|| "D-Bus message too large".to_string())?;
+
+        let mut raw = vec![0u8; total];
+        raw[..16].copy_from_slice(&fixed);
+        self.read_exact(&mut raw[16..])?;
+        Message::decode(raw)
+    }
+
+    fn read_auth_line(&mut self) -> Result<String, String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        while !line.ends_with(b"\r\n") {
+            self.read_exact(&mut byte)?;
+            line.push(byte[0]);
+            if line.len() > 4096 {
+                return Err("D-Bus authentication reply too long".to_string());
+            }
+        }
+        line.truncate(line.len() - 2);
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(bytes)
+            .map_err(|e| format!("failed to write to D-Bus: {e}"))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        self.stream
+            .read_exact(buf)
+            .map_err(|e| format!("failed to read from D-Bus: {e}"))
+    }
+}
+
+fn connect_unix(entry: &str) -> Result<UnixStream, String> {
+    let (transport, params) = entry
+        .split_once(':')
+        .ok_or_else(|| format!("invalid D-Bus address {entry:?}"))?;
+    if transport != "unix" {
+        return Err(format!("unsupported D-Bus transport {transport:?}"));
+    }
+
+    let param = |key: &str| {
+        params
+            .split(',')
+            .find_map(|kv| kv.strip_prefix(key)?.strip_prefix('='))
+            .map(unescape_address_value)
+    };
+    let connect_err = |e: io::Error| format!("failed to connect to D-Bus at {entry:?}: {e}");
+
+    let stream = if let Some(path) = param("path") {
+        UnixStream::connect(path).map_err(connect_err)?
+    } else if let Some(name) = param("abstract") {
+        connect_abstract(&name).map_err(connect_err)?
+    } else {
+        return Err(format!("unsupported D-Bus address {entry:?}"));
+    };
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .map_err(connect_err)?;
+    stream
+        .set_write_timeout(Some(IO_TIMEOUT))
+        .map_err(connect_err)?;
+    Ok(stream)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn connect_abstract(name: &str) -> io::Result<UnixStream> {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+    UnixStream::connect_addr(&addr)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn connect_abstract(_name: &str) -> io::Result<UnixStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract sockets are Linux-only",
+    ))
+}
+
+/// Undoes the `%XX` escaping allowed in address values.
+fn unescape_address_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(value.get(i + 1..i + 3).unwrap_or(""), 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Urgency {
+    Normal = 1,
+    Critical = 2,
+}
+
+/// One call to `org.freedesktop.Notifications.Notify`.
+#[derive(Clone, Debug)]
+pub(crate) struct DesktopNotification<'a> {
+    pub(crate) app_name: &'a str,
+    pub(crate) icon: &'a str,
+    pub(crate) summary: &'a str,
+    pub(crate) body: &'a str,
+    pub(crate) urgency: Urgency,
+    /// Sound theme name passed as the `sound-name` hint, if any.
+    pub(crate) sound: Option<&'a str>,
+    /// Milliseconds; -1 lets the server decide and 0 means never expire.
+    pub(crate) expire_timeout_ms: i32,
+}
+
+/// Shows a desktop notification and returns the id the server assigned to it.
+pub(crate) fn notify(
+    conn: &mut Connection,
+    notification: &DesktopNotification,
+) -> Result<u32, String> {
+    let mut hints = vec![("urgency", Value::Byte(notification.urgency as u8))];
+    if let Some(sound) = notification.sound {
+        hints.push(("sound-name", Value::Str(sound.to_string())));
+    }
+    let body = [
+        Value::Str(notification.app_name.to_string()),
+        Value::U32(0),
+        Value::Str(notification.icon.to_string()),
+        Value::Str(notification.summary.to_string()),
+        Value::Str(notification.body.to_string()),
+        Value::Array("s".to_string(), Vec::new()),
+        string_variant_dict(hints),
+        Value::I32(notification.expire_timeout_ms),
+    ];
+    let message = Message::method_call(
+        NOTIFICATIONS_NAME,
+        NOTIFICATIONS_PATH,
+        NOTIFICATIONS_NAME,
+        "Notify",
+    );
+    let reply = conn
+        .call(message, &body)
+        .map_err(|e| format!("Notify failed: {e}"))?;
+    reply
+        .first()
+        .and_then(Value::as_u32)
+        .ok_or_else(|| "unexpected reply to Notify".to_string())
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn align(&mut self, n: usize) {
+        while !self.buf.len().is_multiple_of(n) {
+            self.buf.push(0);
+        }
+    }
+
+    fn put_u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    fn put_u32(&mut self, v: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn put_str(&mut self, s: &str) {
+        self.put_u32(s.len() as u32);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn put_signature(&mut self, s: &str) {
+        self.put_u8(s.len() as u8);
+        self.buf.extend_from_slice(s.as_bytes());
+        self.buf.push(0);
+    }
+
+    fn put_value(&mut self, value: &Value) {
+        match value {
+            Value::Byte(v) => self.put_u8(*v),
+            Value::Bool(v) => self.put_u32(u32::from(*v)),
+            Value::I32(v) => self.put_u32(*v as u32),
+            Value::U32(v) => self.put_u32(*v),
+            Value::Str(s) | Value::ObjectPath(s) => self.put_str(s),
+            Value::Signature(s) => self.put_signature(s),
+            Value::Array(elem, items) => {
+                self.put_u32(0);
+                let len_pos = self.buf.len() - 4;
+                // Padding before the first element is not part of the length.
+                self.align(alignment(elem));
+                let start = self.buf.len();
+                for item in items {
+                    self.put_value(item);
+                }
+                let len = (self.buf.len() - start) as u32;
+                self.buf[len_pos..len_pos + 4].copy_from_slice(&len.to_le_bytes());
+            }
+            Value::Struct(fields) => {
+                self.align(8);
+                for field in fields {
+                    self.put_value(field);
+                }
+            }
+            Value::DictEntry(key, value) => {
+                self.align(8);
+                self.put_value(key);
+                self.put_value(value);
+            }
+            Value::Variant(inner) => {
+                self.put_signature(&inner.signature());
+                self.put_value(inner);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn align(&mut self, n: usize) -> Result<(), String> {
+        let aligned = self.pos.next_multiple_of(n);
+        if aligned > self.buf.len() {
+            return Err("truncated D-Bus message".to_string());
+        }
+        self.pos = aligned;
+        Ok(())
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.buf.len())
+            .ok_or_else(|| "truncated D-Bus message".to_string())?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn get_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn get_u32(&mut self) -> Result<u32, String> {
+        self.align(4)?;
+        let bytes: [u8; 4] = self.take(4)?.try_into().expect("took 4 bytes");
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn get_string(&mut self, len: usize) -> Result<String, String> {
+        let bytes = self.take(len)?.to_vec();
+        self.take(1)?;
+        String::from_utf8(bytes).map_err(|_| "invalid UTF-8 in D-Bus message".to_string())
+    }
+
+    fn read_value(&mut self, ty: &str) -> Result<Value, String> {
+        let unsupported = || format!("unsupported D-Bus type {ty:?}");
+        match ty.as_bytes().first().ok_or_else(unsupported)? {
+            b'y' => Ok(Value::Byte(self.get_u8()?)),
+            b'b' => Ok(Value::Bool(self.get_u32()? != 0)),
+            b'i' => Ok(Value::I32(self.get_u32()? as i32)),
+            b'u' => Ok(Value::U32(self.get_u32()?)),
+            b's' => {
+                let len = self.get_u32()? as usize;
+                Ok(Value::Str(self.get_string(len)?))
+            }
+            b'o' => {
+                let len = self.get_u32()? as usize;
+                Ok(Value::ObjectPath(self.get_string(len)?))
+            }
+            b'g' => {
+                let len = self.get_u8()? as usize;
+                Ok(Value::Signature(self.get_string(len)?))
+            }
+            b'v' => {
+                let len = self.get_u8()? as usize;
+                let signature = self.get_string(len)?;
+                let (inner, rest) = split_first_type(&signature)?;
+                if !rest.is_empty() {
+                    return Err("invalid variant signature".to_string());
+                }
+                Ok(Value::Variant(Box::new(self.read_value(inner)?)))
+            }
+            b'a' => {
+                let elem = &ty[1..];
+                let len = self.get_u32()? as usize;
+                self.align(alignment(elem))?;
+                let end = self
+                    .pos
+                    .checked_add(len)
+                    .filter(|&end| end <= self.buf.len())
+                    .ok_or_else(|| "truncated D-Bus message".to_string())?;
+                let mut items = Vec::new();
+                while self.pos < end {
+                    let start = self.pos;
+                    items.push(self.read_value(elem)?);
+                    // An element that takes no bytes would never reach the end.
+                    if self.pos == start {
+                        return Err(format!("invalid D-Bus array element {elem:?}"));
+                    }
+                }
+                Ok(Value::Array(elem.to_string(), items))
+            }
+            b'(' => {
+                self.align(8)?;
+                let mut fields = Vec::new();
+                let mut rest = &ty[1..ty.len() - 1];
+                while !rest.is_empty() {
+                    let (field, tail) = split_first_type(rest)?;
+                    fields.push(self.read_value(field)?);
+                    rest = tail;
+                }
+                Ok(Value::Struct(fields))
+            }
+            b'{' => {
+                self.align(8)?;
+                let inner = &ty[1..ty.len() - 1];
+                let (key, value) = split_first_type(inner)?;
+                Ok(Value::DictEntry(
+                    Box::new(self.read_value(key)?),
+                    Box::new(self.read_value(value)?),
+                ))
+            }
+            _ => Err(unsupported()),
+        }
+    }
+}
+
+fn alignment(ty: &str) -> usize {
+    match ty.as_bytes().first() {
+        Some(b'y' | b'g' | b'v') => 1,
+        Some(b'(' | b'{') => 8,
+        _ => 4,
+    }
+}
+
+/// Splits a signature into its first complete type and the remainder.
+fn split_first_type(sig: &str) -> Result<(&str, &str), String> {
+    let invalid = || format!("invalid D-Bus signature {sig:?}");
+    let len = match sig.as_bytes().first().ok_or_else(invalid)? {
+        b'a' => 1 + split_first_type(&sig[1..])?.0.len(),
+        open @ (b'(' | b'{') => {
+            let close = if *open == b'(' { b')' } else { b'}' };
+            let mut depth = 0;
+            let end = sig
+                .bytes()
+                .position(|b| {
+                    if b == *open {
+                        depth += 1;
+                    } else if b == close {
+                        depth -= 1;
+                    }
+                    depth == 0
+                })
+                .ok_or_else(invalid)?;
+            // Empty structs and dict entries aren't valid types.
+            if end < 2 {
+                return Err(invalid());
+            }
+            end + 1
+        }
+        _ => 1,
+    };
+    Ok(sig.split_at(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(ty: &str, buf: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader {
+            buf,
+            pos: 0,
+            big_endian: false,
+        };
+        reader.read_value(ty)
+    }
+
+    #[test]
+    fn empty_structs_are_rejected() {
+        assert!(split_first_type("()").is_err());
+        assert!(split_first_type("a()").is_err());
+        assert!(split_first_type("{}").is_err());
+        assert_eq!(split_first_type("(y)u"), Ok(("(y)", "u")));
+
+        // Eight bytes of array holding elements that take none.
+        let buf = [8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(read("a()", &buf).is_err());
+        assert_eq!(
+            read("ay", &[2, 0, 0, 0, 7, 9]),
+            Ok(Value::Array(
+                "y".to_string(),
+                vec![Value::Byte(7), Value::Byte(9)]
+            ))
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! The daily pomodoro goal and the streak of days it was met.
+//!
+//! Both are worked out from the session log: a day's pomodoros are the work
+//! sessions completed during it. Days begin at `goal.rollover_hour`, so with
+//! a rollover at 4 a pomodoro finished at 1am counts toward the day before.
+//! The streak measures every past day against the current goal.
+
+use crate::config::{self, GoalConfig};
+use crate::log::{self, LogEntry, Summary};
+use crate::{add_local_days_at, config_path, local_day_start_at, log_path, now_unix};
+use clap::Subcommand;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const MAX_DAILY_GOAL: u64 = 100;
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum GoalCommand {
+    /// Set the daily goal in completed pomodoros
+    Set { pomodoros: u64 },
+    /// Remove the daily goal
+    Clear,
+    /// Show the goal and today's progress
+    Show,
+}
+
+/// Today's completed pomodoros against the daily goal.
+#[derive(Copy, Clone, Debug, Serialize)]
+pub(crate) struct Progress {
+    pub(crate) today: u64,
+    pub(crate) daily: u64,
+}
+
+impl Progress {
+    /// Progress for the day containing `now`; `None` when no goal is set.
+    pub(crate) fn load(now: i64) -> Result<Option<Progress>, String> {
+        let goal = config::load(&config_path()?)?.goal;
+        let Some(daily) = goal.daily else {
+            return Ok(None);
+        };
+        let entries = log::read_entries(&log_path()?)?;
+        let day_start = local_day_start_at(now, goal.rollover_hour)?;
+        let day_end = add_local_days_at(day_start, 1, goal.rollover_hour)?;
+        let today = Summary::for_range(&entries, day_start, day_end).pomodoros;
+        Ok(Some(Progress { today, daily }))
+    }
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}/{1}", self.today, self.daily)
+    }
+}
+
+pub(crate) fn goal_command(command: GoalCommand) -> Result<i32, String> {
+    let path = config_path()?;
+    match command {
+        GoalCommand::Set { pomodoros } => {
+            validate_goal(pomodoros)?;
+            config::update(&path, "goal", "daily", Some(&pomodoros.to_string()))?;
+            println!("daily goal: {pomodoros} pomodoros");
+        }
+        GoalCommand::Clear => {
+            config::update(&path, "goal", "daily", None)?;
+            println!("daily goal cleared");
+        }
+        GoalCommand::Show => {
+            let Some(progress) = Progress::load(now_unix())? else {
+                println!("no daily goal set");
+                return Ok(1);
+            };
+            println!("goal: {0} pomodoros a day", progress.daily);
+            println!("today: {progress}");
+        }
+    }
+    Ok(0)
+}
+
+pub(crate) fn validate_goal(pomodoros: u64) -> Result<(), String> {
+    if pomodoros == 0 {
+        return Err("goal must be > 0 (use `tock goal clear` to remove it)".to_string());
+    }
+    if pomodoros > MAX_DAILY_GOAL {
+        return Err(format!("goal too large (max {MAX_DAILY_GOAL})"));
+    }
+    Ok(())
+}
+
+/// Prints how many days in a row, up to today, the goal was met.
+pub(crate) fn streak() -> Result<i32, String> {
+    let goal = config::load(&config_path()?)?.goal;
+    let Some(daily) = goal.daily else {
+        println!("no daily goal set (see `tock goal set`)");
+        return Ok(1);
+    };
+    let entries = log::read_entries(&log_path()?)?;
+    let streak = count_streak(&entries, &goal, now_unix())?;
+
+    let days = if streak.days == 1 { "day" } else { "days" };
+    println!("streak: {0} {days}", streak.days);
+    println!("today: {0}/{daily}", streak.today);
+    Ok(0)
+}
+
+struct Streak {
+    /// Days in a row the goal was met.
+    days: u64,
+    /// Pomodoros completed so far today.
+    today: u64,
+}
+
+/// The streak as of `now`. Today only counts once its goal is met, but until
+/// the day is over it doesn't break the streak either. No day is met without
+/// a goal.
+fn count_streak(entries: &[LogEntry], goal: &GoalConfig, now: i64) -> Result<Streak, String> {
+    let per_day = pomodoros_per_day(entries, goal)?;
+    let met = |day: i64| match (per_day.get(&day), goal.daily) {
+        (Some(&pomodoros), Some(daily)) => pomodoros >= daily,
+        _ => false,
+    };
+
+    let hour = goal.rollover_hour;
+    let today = local_day_start_at(now, hour)?;
+    let mut day = if met(today) {
+        today
+    } else {
+        add_local_days_at(today, -1, hour)?
+    };
+    let mut days = 0;
+    while met(day) {
+        days += 1;
+        day = add_local_days_at(day, -1, hour)?;
+    }
+    Ok(Streak {
+        days,
+        today: per_day.get(&today).copied().unwrap_or(0),
+    })
+}
+
+/// Completed pomodoros by the start of the day they count toward.
+fn pomodoros_per_day(entries: &[LogEntry], goal: &GoalConfig) -> Result<HashMap<i64, u64>, String> {
+    let mut per_day = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.is_completed_pomodoro()) {
+        let day = local_day_start_at(entry.end_ts, goal.rollover_hour)?;
+        *per_day.entry(day).or_insert(0) += 1;
+    }
+    Ok(per_day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{in_tz, session, CET, JAN_10_10AM};
+    use crate::Mode;
+
+    const HOUR: i64 = 3600;
+    const DAY: i64 = 24 * HOUR;
+    /// 2026-01-10 00:00 in `CET`.
+    const JAN_10: i64 = JAN_10_10AM - 10 * HOUR;
+
+    fn pomodoro_at(end_ts: i64) -> LogEntry {
+        session(Mode::Work, end_ts - 1500, end_ts, 0)
+    }
+
+    fn goal(daily: u64, rollover_hour: u32) -> GoalConfig {
+        GoalConfig {
+            daily: Some(daily),
+            rollover_hour,
+        }
+    }
+
+    fn streak_at(entries: &[LogEntry], goal: &GoalConfig, now: i64) -> (u64, u64) {
+        let streak = in_tz(CET, || count_streak(entries, goal, now)).unwrap();
+        (streak.days, streak.today)
+    }
+
+    #[test]
+    fn late_pomodoros_count_toward_the_day_before() {
+        let entries = [pomodoro_at(JAN_10 + HOUR), pomodoro_at(JAN_10 + 5 * HOUR)];
+        let per_day = in_tz(CET, || pomodoros_per_day(&entries, &goal(1, 4))).unwrap();
+        let jan_9_4am = JAN_10 - DAY + 4 * HOUR;
+        assert_eq!(per_day.get(&jan_9_4am), Some(&1));
+        assert_eq!(per_day.get(&(jan_9_4am + DAY)), Some(&1));
+
+        let per_day = in_tz(CET, || pomodoros_per_day(&entries, &goal(1, 0))).unwrap();
+        assert_eq!(per_day.get(&JAN_10), Some(&2));
+    }
+
+    #[test]
+    fn only_completed_work_counts() {
+        let mut stopped = pomodoro_at(JAN_10 + 8 * HOUR);
+        stopped.end_reason = crate::log::EndReason::Stopped;
+        let short_break = session(Mode::Break, JAN_10 + 8 * HOUR, JAN_10 + 9 * HOUR, 0);
+        assert_eq!(
+            streak_at(&[stopped, short_break], &goal(1, 0), JAN_10_10AM),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn today_does_not_break_the_streak_until_it_is_over() {
+        let goal = goal(2, 4);
+        // Two a day on the 7th, 8th and 9th; the 9th's second at 01:00 on the 10th.
+        let mut entries: Vec<LogEntry> = (1..=3)
+            .flat_map(|days_ago| {
+                let day = JAN_10 - days_ago * DAY;
+                [pomodoro_at(day + 10 * HOUR), pomodoro_at(day + 11 * HOUR)]
+            })
+            .collect();
+        entries[1] = pomodoro_at(JAN_10 + HOUR);
+        assert_eq!(streak_at(&entries, &goal, JAN_10_10AM), (3, 0));
+
+        entries.push(pomodoro_at(JAN_10 + 9 * HOUR));
+        assert_eq!(streak_at(&entries, &goal, JAN_10_10AM), (3, 1));
+        entries.push(pomodoro_at(JAN_10 + 10 * HOUR));
+        assert_eq!(streak_at(&entries, &goal, JAN_10_10AM), (4, 2));
+
+        // The next day, before its rollover, is still the 10th.
+        assert_eq!(streak_at(&entries, &goal, JAN_10 + DAY + 3 * HOUR), (4, 2));
+        // After it, the 10th is yesterday.
+        assert_eq!(streak_at(&entries, &goal, JAN_10 + DAY + 5 * HOUR), (4, 0));
+        // A day with nothing ends it.
+        assert_eq!(
+            streak_at(&entries, &goal, JAN_10 + 2 * DAY + 5 * HOUR),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn a_missed_day_ends_the_streak() {
+        let goal = goal(1, 0);
+        let entries = [
+            pomodoro_at(JAN_10 - 3 * DAY + HOUR),
+            pomodoro_at(JAN_10 - DAY + HOUR),
+        ];
+        assert_eq!(streak_at(&entries, &goal, JAN_10_10AM), (1, 0));
+        let no_goal = GoalConfig::default();
+        assert_eq!(streak_at(&entries, &no_goal, JAN_10_10AM), (0, 0));
+    }
+}
@@ -0,0 +1,263 @@
+//! User scripts run when a session changes.
+//!
+//! A hook is an executable named after its event in
+//! `$XDG_CONFIG_HOME/tock/hooks/`. It gets the session as `TOCK_*` environment
+//! variables and as JSON on stdin. Hooks run synchronously but are killed after
+//! `HOOK_TIMEOUT`, so a hung script can't hold up the timer.
+
+use crate::log::EndReason;
+use crate::State;
+use std::io::{self, Write};
+use std::os::fd::AsFd;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Hook {
+    /// A work or break session began, including each phase of a sequence.
+    SessionStart,
+    /// A session ended, for any reason.
+    SessionEnd,
+    /// A sequence moved on to its next phase.
+    PhaseChange,
+    Stopped,
+    Paused,
+    Resumed,
+    /// The last phase of a sequence finished.
+    CycleComplete,
+}
+
+impl Hook {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Hook::SessionStart => "session_start",
+            Hook::SessionEnd => "session_end",
+            Hook::PhaseChange => "phase_change",
+            Hook::Stopped => "stopped",
+            Hook::Paused => "paused",
+            Hook::Resumed => "resumed",
+            Hook::CycleComplete => "cycle_complete",
+        }
+    }
+}
+
+/// Runs the hook for `hook` in `dir`, if there is one. `end_reason` is set for
+/// events that end a session.
+pub(crate) fn run(
+    dir: &Path,
+    hook: Hook,
+    state: &State,
+    end_reason: Option<EndReason>,
+) -> Result<(), String> {
+    let path = dir.join(hook.as_str());
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    // Hook output goes to stderr so it never mixes with tock's own output.
+    let stdout = io::stderr()
+        .as_fd()
+        .try_clone_to_owned()
+        .map(Stdio::from)
+        .unwrap_or_else(|_| Stdio::null());
+
+    let mut child = Command::new(&path)
+        .envs(env_vars(hook, state, end_reason))
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        // Its own process group, so a timeout also kills anything it started.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("failed to run hook {path:?}: {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its stdin is fine.
+        let _ = stdin.write_all(payload(hook, state, end_reason).to_string().as_bytes());
+    }
+
+    wait_or_kill(&mut child, &format!("hook {path:?}"))
+}
+
+/// Waits for `child`, which must lead a process group of its own, and kills
+/// the group if it is still running after `HOOK_TIMEOUT`. `what` names it in
+/// errors. The command notifier runs its command the same way.
+pub(crate) fn wait_or_kill(child: &mut Child, what: &str) -> Result<(), String> {
+    wait_or_kill_after(child, what, HOOK_TIMEOUT)
+}
+
+fn wait_or_kill_after(child: &mut Child, what: &str, timeout: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("{what} failed ({status})")),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                unsafe {
+                    libc::kill(-(child.id() as i32), libc::SIGKILL);
+                }
+                let _ = child.wait();
+                return Err(format!("{what} timed out after {0}s", timeout.as_secs()));
+            }
+            Err(e) => return Err(format!("failed to wait for {what}: {e}")),
+        }
+    }
+}
+
+/// What a hook reads on stdin.
+fn payload(hook: Hook, state: &State, end_reason: Option<EndReason>) -> serde_json::Value {
+    serde_json::json!({
+        "event": hook.as_str(),
+        "end_reason": end_reason.map(EndReason::as_str),
+        "state": state,
+    })
+}
+
+fn env_vars(
+    hook: Hook,
+    state: &State,
+    end_reason: Option<EndReason>,
+) -> Vec<(&'static str, String)> {
+    let mut vars = vec![
+        ("TOCK_EVENT", hook.as_str().to_string()),
+        ("TOCK_PID", state.pid.to_string()),
+        ("TOCK_MODE", state.mode.as_str().to_string()),
+        ("TOCK_START_TS", state.start_ts.to_string()),
+        ("TOCK_END_TS", state.end_ts.to_string()),
+        ("TOCK_SECS", state.secs.to_string()),
+        ("TOCK_MINUTES", state.secs.div_ceil(60).to_string()),
+        ("TOCK_PAUSED", state.paused.is_some().to_string()),
+        ("TOCK_PAUSED_SECS", state.paused_secs.to_string()),
+    ];
+    if let Some(reason) = end_reason {
+        vars.push(("TOCK_END_REASON", reason.as_str().to_string()));
+    }
+    if let Some(sequence) = &state.sequence {
+        vars.push(("TOCK_CYCLE_SET", sequence.set().to_string()));
+        vars.push(("TOCK_CYCLE_SETS", sequence.sets().to_string()));
+        vars.push(("TOCK_STEP", sequence.step().to_string()));
+        vars.push(("TOCK_STEPS", sequence.steps().to_string()));
+    }
+    if let Some(task) = &state.meta.task {
+        vars.push(("TOCK_TASK", task.clone()));
+    }
+    if !state.meta.tags.is_empty() {
+        vars.push(("TOCK_TAGS", state.meta.tags.join(",")));
+    }
+    if let Some(note) = &state.meta.note {
+        vars.push(("TOCK_NOTE", note.clone()));
+    }
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Sequence;
+    use crate::tests::running;
+    use crate::Mode;
+
+    #[test]
+    fn hooks_get_the_session_as_env_vars() {
+        let mut state = running(Mode::Work, 1000, 1500);
+        state.sequence = Some(Sequence::parse("(25w 5b)x2").unwrap());
+        state.meta.task = Some("report".to_string());
+        state.meta.tags = vec!["a".to_string(), "b".to_string()];
+        state.meta.note = Some("draft".to_string());
+        state.paused_secs = 30;
+
+        let vars = env_vars(Hook::SessionEnd, &state, Some(EndReason::Stopped));
+        let expected = [
+            ("TOCK_EVENT", "session_end"),
+            ("TOCK_PID", "42"),
+            ("TOCK_MODE", "work"),
+            ("TOCK_START_TS", "1000"),
+            ("TOCK_END_TS", "2500"),
+            ("TOCK_SECS", "1500"),
+            ("TOCK_MINUTES", "25"),
+            ("TOCK_PAUSED", "false"),
+            ("TOCK_PAUSED_SECS", "30"),
+            ("TOCK_END_REASON", "stopped"),
+            ("TOCK_CYCLE_SET", "1"),
+            ("TOCK_CYCLE_SETS", "2"),
+            ("TOCK_STEP", "1"),
+            ("TOCK_STEPS", "4"),
+            ("TOCK_TASK", "report"),
+            ("TOCK_TAGS", "a,b"),
+            ("TOCK_NOTE", "draft"),
+        ];
+        let vars: Vec<_> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        assert_eq!(vars, expected);
+
+        let vars = env_vars(Hook::SessionStart, &running(Mode::Break, 0, 90), None);
+        let names: Vec<_> = vars.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "TOCK_EVENT",
+                "TOCK_PID",
+                "TOCK_MODE",
+                "TOCK_START_TS",
+                "TOCK_END_TS",
+                "TOCK_SECS",
+                "TOCK_MINUTES",
+                "TOCK_PAUSED",
+                "TOCK_PAUSED_SECS",
+            ]
+        );
+        assert!(vars.contains(&("TOCK_MINUTES", "2".to_string())));
+    }
+
+    #[test]
+    fn hooks_get_the_session_as_json() {
+        let mut state = running(Mode::Work, 1000, 1500);
+        state.meta.task = Some("report".to_string());
+        let json = payload(Hook::Stopped, &state, Some(EndReason::Stopped));
+        assert_eq!(json["event"], "stopped");
+        assert_eq!(json["end_reason"], "stopped");
+        assert_eq!(json["state"]["pid"], 42);
+        assert_eq!(json["state"]["mode"], "work");
+        assert_eq!(json["state"]["start_ts"], 1000);
+        assert_eq!(json["state"]["end_ts"], 2500);
+        assert_eq!(json["state"]["secs"], 1500);
+        assert_eq!(json["state"]["paused_secs"], 0);
+        assert_eq!(json["state"]["task"], "report");
+
+        let json = payload(Hook::Paused, &state, None);
+        assert_eq!(json["event"], "paused");
+        assert!(json["end_reason"].is_null());
+    }
+
+    #[test]
+    fn a_hook_past_its_timeout_is_killed() {
+        let marker = std::env::temp_dir().join(format!("tock-hook-{}", std::process::id()));
+        let mut child = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(r#"(sleep 0.5; touch "$1") & sleep 30"#)
+            .arg("sh")
+            .arg(&marker)
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        let result = wait_or_kill_after(&mut child, "hook", Duration::from_millis(100));
+        assert_eq!(result, Err("hook timed out after 0s".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // What the hook started went with it.
+        std::thread::sleep(Duration::from_secs(1));
+        assert!(!marker.exists());
+
+        let mut child = Command::new("/bin/sh")
+            .arg("-c")
+            .arg("exit 3")
+            .spawn()
+            .unwrap();
+        let result = wait_or_kill_after(&mut child, "hook", Duration::from_secs(5));
+        assert!(result.unwrap_err().starts_with("hook failed"));
+    }
+}
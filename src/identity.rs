@@ -0,0 +1,246 @@
+//! Telling a timer's daemon apart from whatever process has its pid now.
+//!
+//! A pid in state.json can outlive its daemon: after a crash, a reboot or a
+//! long suspend, another process may have been given the same number. Before
+//! `tock` reports a timer as running or sends its daemon a signal, it checks
+//! that the process still has the start time and boot recorded in the state,
+//! and that its command line is `__run` with the session's token.
+//!
+//! Linux answers from `/proc`, macOS from `proc_pidinfo` and `sysctl`. Elsewhere
+//! only the pid is checked, as before.
+
+use crate::State;
+use platform::{boot_id, command_line, is_zombie, start_time};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+
+/// The subcommand the daemon runs as.
+const RUN_SUBCOMMAND: &str = "__run";
+const TOKEN_BYTES: usize = 16;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct Identity {
+    /// When the process started: clock ticks since boot on Linux, unix
+    /// microseconds on macOS.
+    start_time: u64,
+    boot_id: String,
+    /// Random, handed to the daemon on its command line as `--token`.
+    token: String,
+}
+
+impl Identity {
+    /// The identity of the running process `pid`, started with `token`. `None`
+    /// where the platform can't tell.
+    pub(crate) fn of(pid: i32, token: &str) -> Option<Identity> {
+        Some(Identity {
+            start_time: start_time(pid)?,
+            boot_id: boot_id()?,
+            token: token.to_string(),
+        })
+    }
+}
+
+/// A fresh session token.
+pub(crate) fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    File::open("/dev/urandom")
+        .and_then(|mut urandom| urandom.read_exact(&mut bytes))
+        .map_err(|e| format!("failed to read /dev/urandom: {e}"))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Whether the daemon that wrote `state` is still running. State written by an
+/// older version has no identity; then any `__run` process with the pid will do.
+pub(crate) fn daemon_running(state: &State) -> bool {
+    let pid = state.pid;
+    if pid <= 0 || !process_exists(pid) {
+        return false;
+    }
+    let args = command_line(pid);
+    let is_run = |token: Option<&str>| {
+        args.as_ref().is_none_or(|args| {
+            args.get(1).is_some_and(|arg| arg == RUN_SUBCOMMAND)
+                && token.is_none_or(|token| args.contains(&format!("--token={token}")))
+        })
+    };
+    match &state.identity {
+        Some(identity) => {
+            Identity::of(pid, &identity.token).as_ref() == Some(identity)
+                && is_run(Some(&identity.token))
+        }
+        None => is_run(None),
+    }
+}
+
+/// Whether there is a process with this pid that hasn't exited. A zombie (a
+/// daemon that exited but wasn't reaped yet) doesn't count where that shows.
+pub(crate) fn process_exists(pid: i32) -> bool {
+    let exists = unsafe { libc::kill(pid, 0) } == 0
+        || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    exists && !is_zombie(pid)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs;
+
+    /// The fields of `/proc/<pid>/stat` after the command name, which may
+    /// itself contain spaces and parentheses.
+    fn stat_fields(pid: i32) -> Option<Vec<String>> {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let (_, rest) = stat.rsplit_once(')')?;
+        Some(rest.split_whitespace().map(str::to_string).collect())
+    }
+
+    pub(super) fn start_time(pid: i32) -> Option<u64> {
+        // Field 22 of stat(5); the first field after the name is field 3.
+        stat_fields(pid)?.get(19)?.parse().ok()
+    }
+
+    pub(super) fn is_zombie(pid: i32) -> bool {
+        stat_fields(pid).is_some_and(|fields| fields.first().is_some_and(|state| state == "Z"))
+    }
+
+    pub(super) fn boot_id() -> Option<String> {
+        let id = fs::read_to_string("/proc/sys/kernel/random/boot_id").ok()?;
+        Some(id.trim().to_string())
+    }
+
+    pub(super) fn command_line(pid: i32) -> Option<Vec<String>> {
+        let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+        Some(
+            cmdline
+                .split(|&byte| byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::CStr;
+
+    fn bsd_info(pid: i32) -> Option<libc::proc_bsdinfo> {
+        let mut info: libc::proc_bsdinfo = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+        let written = unsafe {
+            libc::proc_pidinfo(
+                pid,
+                libc::PROC_PIDTBSDINFO,
+                0,
+                (&mut info as *mut libc::proc_bsdinfo).cast(),
+                size,
+            )
+        };
+        (written == size).then_some(info)
+    }
+
+    pub(super) fn start_time(pid: i32) -> Option<u64> {
+        let info = bsd_info(pid)?;
+        Some(info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec)
+    }
+
+    pub(super) fn is_zombie(pid: i32) -> bool {
+        bsd_info(pid).is_some_and(|info| info.pbi_status == libc::SZOMB)
+    }
+
+    pub(super) fn boot_id() -> Option<String> {
+        let mut buf = [0u8; 64];
+        let mut len = buf.len();
+        let res = unsafe {
+            libc::sysctlbyname(
+                c"kern.bootsessionuuid".as_ptr(),
+                buf.as_mut_ptr().cast(),
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if res == -1 {
+            return None;
+        }
+        let id = CStr::from_bytes_until_nul(&buf[..len]).ok()?;
+        Some(id.to_string_lossy().into_owned())
+    }
+
+    /// From `KERN_PROCARGS2`: argc, the executable path, padding, then the
+    /// arguments, each NUL-terminated.
+    pub(super) fn command_line(pid: i32) -> Option<Vec<String>> {
+        let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid];
+        let mut size = 0usize;
+        let mut sysctl = |buf: *mut libc::c_void, size: &mut usize| unsafe {
+            libc::sysctl(mib.as_mut_ptr(), 3, buf, size, std::ptr::null_mut(), 0)
+        };
+        if sysctl(std::ptr::null_mut(), &mut size) == -1 {
+            return None;
+        }
+        let mut buf = vec![0u8; size];
+        if sysctl(buf.as_mut_ptr().cast(), &mut size) == -1 {
+            return None;
+        }
+        buf.truncate(size);
+
+        let argc = i32::from_ne_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+        let rest = &buf[4..];
+        let exe_end = rest.iter().position(|&byte| byte == 0)?;
+        let args_start = exe_end + rest[exe_end..].iter().position(|&byte| byte != 0)?;
+        Some(
+            rest[args_start..]
+                .split(|&byte| byte == 0)
+                .take(argc)
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod platform {
+    pub(super) fn start_time(_pid: i32) -> Option<u64> {
+        None
+    }
+
+    pub(super) fn is_zombie(_pid: i32) -> bool {
+        false
+    }
+
+    pub(super) fn boot_id() -> Option<String> {
+        None
+    }
+
+    pub(super) fn command_line(_pid: i32) -> Option<Vec<String>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::running;
+    use crate::Mode;
+    use std::process::Command;
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn a_process_that_is_not_the_daemon_is_rejected() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id() as i32;
+        let mut state = running(Mode::Work, 0, 1500);
+        state.pid = pid;
+        state.identity = Identity::of(pid, "0123");
+        // Alive, and with the recorded start time and boot, but its command
+        // line isn't `__run --token=0123`.
+        assert!(state.identity.is_some());
+        assert!(process_exists(pid));
+        assert!(!daemon_running(&state));
+        state.identity = None;
+        assert!(!daemon_running(&state));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(!process_exists(pid));
+    }
+}
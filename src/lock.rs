@@ -0,0 +1,94 @@
+//! An advisory lock on the state directory.
+//!
+//! Anything that reads state.json and then acts on what it found (stopping the
+//! daemon it names, clearing it, starting a daemon that replaces it) holds the
+//! lock for the whole of that, so two `tock start`s can't both decide they are
+//! the one to replace the timer. The lock is a `flock` on a file next to the
+//! state file; it goes away with the process holding it, so a crash can't leave
+//! it stuck.
+//!
+//! A daemon never waits for the lock while serving a request from a holder:
+//! its first state write happens while the `tock start` that spawned it holds
+//! the lock, and `stop` requests only come from commands holding it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOCK_FILE: &str = "state.lock";
+/// How often `acquire_within` tries again.
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Holds the lock until dropped.
+pub(crate) struct StateLock {
+    _file: File,
+}
+
+impl StateLock {
+    /// Waits for the lock on the directory holding `state_path`.
+    pub(crate) fn acquire(state_path: &Path) -> Result<StateLock, String> {
+        let file = open(state_path)?;
+        loop {
+            match flock(&file, libc::LOCK_EX) {
+                Ok(()) => return Ok(StateLock { _file: file }),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(lock_error(state_path, e)),
+            }
+        }
+    }
+
+    /// Takes the lock if nobody holds it.
+    pub(crate) fn try_acquire(state_path: &Path) -> Result<Option<StateLock>, String> {
+        let file = open(state_path)?;
+        match flock(&file, libc::LOCK_EX | libc::LOCK_NB) {
+            Ok(()) => Ok(Some(StateLock { _file: file })),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(lock_error(state_path, e)),
+        }
+    }
+
+    /// Waits up to `timeout` for the lock, returning `None` if it is still held.
+    pub(crate) fn acquire_within(
+        state_path: &Path,
+        timeout: Duration,
+    ) -> Result<Option<StateLock>, String> {
+        let give_up = Instant::now() + timeout;
+        loop {
+            if let Some(lock) = StateLock::try_acquire(state_path)? {
+                return Ok(Some(lock));
+            }
+            if Instant::now() >= give_up {
+                return Ok(None);
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+fn open(state_path: &Path) -> Result<File, String> {
+    let dir = state_path
+        .parent()
+        .ok_or_else(|| format!("invalid state path {state_path:?}"))?;
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create state dir {dir:?}: {e}"))?;
+    let path = dir.join(LOCK_FILE);
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("failed to open lock file {path:?}: {e}"))
+}
+
+fn flock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn lock_error(state_path: &Path, e: io::Error) -> String {
+    format!("failed to lock the state dir of {state_path:?}: {e}")
+}
@@ -0,0 +1,453 @@
+use crate::sequence::Sequence;
+use crate::{Meta, Mode, State};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Columns are only ever added at the end, so the header of a log started by
+/// an older version is a prefix of this one and stays as it was.
+const HEADER: &str =
+    "id,mode,planned_minutes,start_ts,end_ts,set,sets,end_reason,task,tags,note,paused_secs,while_suspended,planned_secs,exit_status,overtime_secs";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum EndReason {
+    Completed,
+    Stopped,
+    ReplacedByNewTimer,
+    Skipped,
+}
+
+impl EndReason {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            EndReason::Completed => "completed",
+            EndReason::Stopped => "stopped",
+            EndReason::ReplacedByNewTimer => "replaced_by_new_timer",
+            EndReason::Skipped => "skipped",
+        }
+    }
+
+    fn parse(s: &str) -> Option<EndReason> {
+        match s {
+            "completed" => Some(EndReason::Completed),
+            "stopped" => Some(EndReason::Stopped),
+            "replaced_by_new_timer" => Some(EndReason::ReplacedByNewTimer),
+            "skipped" => Some(EndReason::Skipped),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct LogEntry {
+    pub(crate) id: String,
+    pub(crate) mode: Mode,
+    pub(crate) planned_secs: u64,
+    pub(crate) start_ts: i64,
+    pub(crate) end_ts: i64,
+    pub(crate) set: Option<u64>,
+    pub(crate) sets: Option<u64>,
+    pub(crate) end_reason: EndReason,
+    pub(crate) meta: Meta,
+    pub(crate) paused_secs: u64,
+    /// The session ran out while the machine was suspended.
+    pub(crate) while_suspended: bool,
+    /// How the command of a `tock box` session exited: its exit code, or 128
+    /// plus the signal that killed it.
+    pub(crate) exit_status: Option<i32>,
+    /// How long the session ran on past its end waiting for `tock next`.
+    pub(crate) overtime_secs: u64,
+}
+
+impl LogEntry {
+    pub(crate) fn from_state(state: &State, end_ts: i64, end_reason: EndReason) -> LogEntry {
+        LogEntry {
+            id: session_id(state),
+            mode: state.mode,
+            planned_secs: state.secs,
+            start_ts: state.start_ts,
+            end_ts,
+            set: state.sequence.as_ref().map(Sequence::set),
+            sets: state.sequence.as_ref().map(Sequence::sets),
+            end_reason,
+            meta: state.meta.clone(),
+            paused_secs: state.paused_secs_at(end_ts),
+            while_suspended: false,
+            exit_status: None,
+            overtime_secs: state.overdue_secs(end_ts).unwrap_or(0),
+        }
+    }
+
+    /// Builds an entry from a CSV record, looking fields up by header name so rows
+    /// written by older versions (with fewer columns) still parse.
+    fn from_record(columns: &HashMap<&str, usize>, record: &[String]) -> Option<LogEntry> {
+        let field = |name: &str| {
+            columns
+                .get(name)
+                .and_then(|&i| record.get(i))
+                .map(String::as_str)
+                .unwrap_or("")
+        };
+        let optional = |name: &str| field(name).parse::<u64>().ok();
+        let text = |name: &str| Some(field(name).to_string()).filter(|s| !s.is_empty());
+
+        Some(LogEntry {
+            id: field("id").to_string(),
+            mode: parse_mode(field("mode"))?,
+            // Logs from before lengths had seconds only have whole minutes.
+            planned_secs: match optional("planned_secs") {
+                Some(secs) => secs,
+                None => field("planned_minutes")
+                    .parse::<u64>()
+                    .ok()?
+                    .saturating_mul(60),
+            },
+            start_ts: field("start_ts").parse().ok()?,
+            end_ts: field("end_ts").parse().ok()?,
+            set: optional("set"),
+            sets: optional("sets"),
+            end_reason: EndReason::parse(field("end_reason"))?,
+            meta: Meta {
+                task: text("task"),
+                tags: field("tags")
+                    .split(',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                note: text("note"),
+            },
+            paused_secs: optional("paused_secs").unwrap_or(0),
+            while_suspended: field("while_suspended") == "1",
+            exit_status: field("exit_status").parse().ok(),
+            overtime_secs: optional("overtime_secs").unwrap_or(0),
+        })
+    }
+
+    /// Seconds this session overlaps the half-open range `[from, to)`.
+    pub(crate) fn secs_within(&self, from: i64, to: i64) -> u64 {
+        (self.end_ts.min(to) - self.start_ts.max(from)).max(0) as u64
+    }
+
+    /// Wall-clock length of the session, pauses included.
+    pub(crate) fn elapsed_secs(&self) -> u64 {
+        (self.end_ts - self.start_ts).max(0) as u64
+    }
+
+    /// Time actually spent in the session, pauses excluded.
+    pub(crate) fn active_secs(&self) -> u64 {
+        self.elapsed_secs().saturating_sub(self.paused_secs)
+    }
+
+    /// Active seconds inside `[from, to)`. The log doesn't say when the pauses
+    /// happened, so they are spread evenly over the session.
+    pub(crate) fn active_secs_within(&self, from: i64, to: i64) -> u64 {
+        let elapsed = self.elapsed_secs();
+        if elapsed == 0 {
+            return 0;
+        }
+        let within = self.secs_within(from, to);
+        (within as u128 * self.active_secs() as u128 / elapsed as u128) as u64
+    }
+
+    pub(crate) fn is_completed_pomodoro(&self) -> bool {
+        self.mode == Mode::Work && self.end_reason == EndReason::Completed
+    }
+
+    fn to_csv_row(&self) -> String {
+        [
+            self.id.clone(),
+            self.mode.as_str().to_string(),
+            self.planned_secs.div_ceil(60).to_string(),
+            self.start_ts.to_string(),
+            self.end_ts.to_string(),
+            self.set.map(|v| v.to_string()).unwrap_or_default(),
+            self.sets.map(|v| v.to_string()).unwrap_or_default(),
+            self.end_reason.as_str().to_string(),
+            self.meta.task.clone().unwrap_or_default(),
+            self.meta.tags.join(","),
+            self.meta.note.clone().unwrap_or_default(),
+            self.paused_secs.to_string(),
+            if self.while_suspended { "1" } else { "0" }.to_string(),
+            self.planned_secs.to_string(),
+            self.exit_status.map(|v| v.to_string()).unwrap_or_default(),
+            self.overtime_secs.to_string(),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// A session is identified by the daemon that ran it and the moment it started.
+/// Skipping can start several phases of a sequence within one second, so
+/// those also carry their mode and step number.
+fn session_id(state: &State) -> String {
+    match &state.sequence {
+        Some(sequence) => format!(
+            "{0}-{1}-{2}-{3}",
+            state.start_ts,
+            state.pid,
+            state.mode.as_str(),
+            sequence.step()
+        ),
+        None => format!("{0}-{1}", state.start_ts, state.pid),
+    }
+}
+
+pub(crate) fn append_entry(path: &Path, entry: &LogEntry) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("invalid log path {path:?}"))?;
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create log dir {dir:?}: {e}"))?;
+
+    let is_empty = match fs::metadata(path) {
+        Ok(metadata) => metadata.len() == 0,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => return Err(format!("failed to stat log file {path:?}: {e}")),
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("failed to open log file {path:?}: {e}"))?;
+
+    // A single write per row keeps concurrent appends from interleaving.
+    let mut buf = String::new();
+    if is_empty {
+        buf.push_str(HEADER);
+        buf.push('\n');
+    }
+    buf.push_str(&entry.to_csv_row());
+    buf.push('\n');
+    file.write_all(buf.as_bytes())
+        .map_err(|e| format!("failed to write log file {path:?}: {e}"))?;
+    Ok(())
+}
+
+/// Reads every parseable row of the log. A missing log is simply empty;
+/// malformed rows are skipped rather than failing the whole read.
+pub(crate) fn read_entries(path: &Path) -> Result<Vec<LogEntry>, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to read log file {path:?}: {e}")),
+    };
+
+    let mut lines = contents.lines();
+    let Some(header) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    // Rows appended after an upgrade carry the newer columns past the end of
+    // an older header; those are named as in `HEADER`.
+    let header = split_csv_line(header);
+    let columns: HashMap<&str, usize> = header
+        .iter()
+        .map(String::as_str)
+        .chain(HEADER.split(',').skip(header.len()))
+        .enumerate()
+        .map(|(i, name)| (name, i))
+        .collect();
+
+    Ok(lines
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| LogEntry::from_record(&columns, &split_csv_line(line)))
+        .collect())
+}
+
+/// Totals for the sessions overlapping a time range.
+#[derive(Debug, Default)]
+pub(crate) struct Summary {
+    pub(crate) work_secs: u64,
+    pub(crate) break_secs: u64,
+    pub(crate) paused_secs: u64,
+    pub(crate) pomodoros: u64,
+}
+
+impl Summary {
+    /// Sums the parts of each session that fall inside `[from, to)`, so a session
+    /// spanning midnight is split between the two days. Paused time is kept apart
+    /// from work and break time. A pomodoro counts toward the range its completion
+    /// falls in.
+    pub(crate) fn for_range(entries: &[LogEntry], from: i64, to: i64) -> Summary {
+        let mut summary = Summary::default();
+        for entry in entries {
+            let active = entry.active_secs_within(from, to);
+            summary.paused_secs += entry.secs_within(from, to) - active;
+            match entry.mode {
+                Mode::Work => summary.work_secs += active,
+                Mode::Break | Mode::LongBreak => summary.break_secs += active,
+            }
+            if entry.is_completed_pomodoro() && entry.end_ts >= from && entry.end_ts < to {
+                summary.pomodoros += 1;
+            }
+        }
+        summary
+    }
+}
+
+fn parse_mode(s: &str) -> Option<Mode> {
+    match s {
+        "work" => Some(Mode::Work),
+        "break" => Some(Mode::Break),
+        "long_break" => Some(Mode::LongBreak),
+        _ => None,
+    }
+}
+
+/// Quotes a field when needed. Line breaks are flattened to spaces so every
+/// record stays on one line.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if !value.contains([',', '"', '\n', '\r']) {
+        return Cow::Borrowed(value);
+    }
+    let escaped = value.replace(['\n', '\r'], " ").replace('"', "\"\"");
+    Cow::Owned(format!("\"{escaped}\""))
+}
+
+/// Splits one CSV line, honouring double-quoted fields with `""` escapes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log(name: &str, contents: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("tock-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.csv");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn entry(meta: Meta) -> LogEntry {
+        LogEntry {
+            id: "1000-42".to_string(),
+            mode: Mode::Work,
+            planned_secs: 90,
+            start_ts: 1000,
+            end_ts: 1150,
+            set: Some(1),
+            sets: Some(4),
+            end_reason: EndReason::Completed,
+            meta,
+            paused_secs: 60,
+            while_suspended: true,
+            exit_status: Some(3),
+            overtime_secs: 5,
+        }
+    }
+
+    #[test]
+    fn csv_fields_round_trip() {
+        let fields = [
+            "plain",
+            "",
+            "a,b",
+            "say \"hi\"",
+            "\"quoted\"",
+            ",",
+            "\"",
+            "",
+        ];
+        let line = fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(split_csv_line(&line), fields);
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("plain"), "plain");
+    }
+
+    #[test]
+    fn csv_fields_keep_to_one_line() {
+        let field = csv_field("two\nlines,\r\nhere");
+        assert!(!field.contains(['\n', '\r']));
+        assert_eq!(split_csv_line(&field), ["two lines,  here"]);
+    }
+
+    #[test]
+    fn entries_round_trip_through_the_log() {
+        let path = temp_log("log-round-trip", "");
+        let meta = Meta {
+            task: Some("write, \"test\"\nplease".to_string()),
+            tags: vec!["a".to_string(), "b c".to_string()],
+            note: None,
+        };
+        append_entry(&path, &entry(meta)).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().next(), Some(HEADER));
+
+        let [read] = &read_entries(&path).unwrap()[..] else {
+            panic!("expected one entry in {contents}");
+        };
+        assert_eq!(read.id, "1000-42");
+        assert_eq!((read.start_ts, read.end_ts), (1000, 1150));
+        assert_eq!((read.set, read.sets), (Some(1), Some(4)));
+        assert_eq!(read.meta.task.as_deref(), Some("write, \"test\" please"));
+        assert_eq!(read.meta.tags, ["a", "b c"]);
+        assert_eq!(read.meta.note, None);
+        assert_eq!((read.planned_secs, read.paused_secs), (90, 60));
+        assert!(read.while_suspended);
+        assert_eq!(read.exit_status, Some(3));
+        assert_eq!(read.overtime_secs, 5);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn older_logs_keep_their_header() {
+        let old = "id,mode,planned_minutes,start_ts,end_ts,set,sets,end_reason,task,tags,note\n\
+                   1-7,break,5,100,400,2,4,skipped,\"a, b\",x,\n";
+        let path = temp_log("log-old-header", old);
+        append_entry(&path, &entry(Meta::default())).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with(old), "{contents}");
+
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        let first = &entries[0];
+        assert_eq!(first.id, "1-7");
+        assert_eq!(first.mode, Mode::Break);
+        assert_eq!(first.planned_secs, 300);
+        assert_eq!((first.start_ts, first.end_ts), (100, 400));
+        assert_eq!(first.end_reason, EndReason::Skipped);
+        assert_eq!(first.meta.task.as_deref(), Some("a, b"));
+        assert_eq!(first.meta.tags, ["x"]);
+        assert_eq!((first.paused_secs, first.overtime_secs), (0, 0));
+        assert_eq!(first.exit_status, None);
+
+        let second = &entries[1];
+        assert_eq!((second.planned_secs, second.paused_secs), (90, 60));
+        assert!(second.while_suspended);
+        assert_eq!(second.exit_status, Some(3));
+        assert_eq!(second.overtime_secs, 5);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
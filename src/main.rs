@@ -1,4 +1,33 @@
-use clap::{Parser, Subcommand, ValueEnum};
+mod bar;
+mod budget;
+mod config;
+mod control;
+mod daemon;
+mod dbus;
+mod goal;
+mod hooks;
+mod identity;
+mod lock;
+mod log;
+mod notify;
+mod prompt;
+mod report;
+mod sequence;
+mod timebox;
+mod watch;
+
+use budget::BudgetCommand;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config::{ConfigCommand, SessionConfig};
+use control::{Request, Response};
+use goal::GoalCommand;
+use hooks::Hook;
+use identity::Identity;
+use lock::StateLock;
+use log::{EndReason, LogEntry, Summary};
+use notify::{Notification, Notifier};
+use report::{Format, OutputArgs, Report};
+use sequence::{RunSequenceArgs, Sequence};
 use serde::{Deserialize, Serialize};
 use std::ffi::{CStr, CString};
 use std::fs;
@@ -7,16 +36,19 @@ use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use timebox::TimeboxArgs;
 
-const DEFAULT_WORK_MINUTES: u64 = 25;
-const DEFAULT_BREAK_MINUTES: u64 = 5;
+const DEFAULT_WORK_SECS: u64 = 25 * 60;
+const DEFAULT_BREAK_SECS: u64 = 5 * 60;
+const DEFAULT_LONG_BREAK_SECS: u64 = 15 * 60;
+const DEFAULT_LONG_BREAK_EVERY: u64 = 4;
 const MAX_MINUTES: u64 = 24 * 60;
 const MAX_SETS: u64 = 100;
 const STATE_DIR: &str = "tock";
 const LEGACY_STATE_DIR: &str = "pomo";
 
 #[derive(Parser, Debug)]
-#[command(version, about = "Ultra-low resource Pomodoro timer (macOS)")]
+#[command(version, about = "Ultra-low resource Pomodoro timer (macOS/Linux)")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
@@ -24,54 +56,261 @@ struct Cli {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Start a work session (default: 25 minutes)
+    /// Start a work session (default: 25 minutes, or as configured)
     Start {
-        /// Session length in minutes
-        minutes: Option<u64>,
-        /// Number of work sessions (auto alternates work/break)
+        /// Session length, e.g. 25m, 90s, 1h15m or 1:30:00 (a bare number is minutes)
+        #[arg(value_parser = parse_duration)]
+        length: Option<u64>,
+        /// Use the settings of a `[profile.NAME]` in the config file
         #[arg(long)]
-        sets: Option<u64>,
-        /// Break length in minutes (used with --sets)
-        #[arg(long = "break-minutes", requires = "sets")]
-        break_minutes: Option<u64>,
+        profile: Option<String>,
+        #[command(flatten)]
+        cycle: CycleArgs,
+        #[command(flatten)]
+        schedule: ScheduleArgs,
+        #[command(flatten)]
+        meta: Meta,
+        #[command(flatten)]
+        output: OutputArgs,
     },
-    /// Start a break session (default: 5 minutes)
+    /// Run a sequence of work and break phases, e.g. "10w (25w 5b)x4 15lb"
+    RunSequence(RunSequenceArgs),
+    /// Run a command under a work session and stop it when time is up, e.g. `box 30m -- make test`
+    #[command(name = "box")]
+    Timebox(TimeboxArgs),
+    /// Start a break session (default: 5 minutes, or as configured)
     Break {
-        /// Session length in minutes
-        minutes: Option<u64>,
+        /// Session length, e.g. 5m or 90s (a bare number is minutes)
+        #[arg(value_parser = parse_duration)]
+        length: Option<u64>,
+        /// Use the settings of a `[profile.NAME]` in the config file
+        #[arg(long)]
+        profile: Option<String>,
+        #[command(flatten)]
+        meta: Meta,
+        #[command(flatten)]
+        output: OutputArgs,
     },
     /// Show current timer status
-    Status,
+    Status {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
     /// Stop the current timer (if running)
-    Stop,
-    #[command(name = "__run", hide = true)]
-    Run {
+    Stop {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Pause the current timer, freezing the remaining time
+    Pause,
+    /// Resume a paused timer
+    Resume,
+    /// Add time to the current phase (e.g. +5m, 90s)
+    Extend {
+        #[arg(value_parser = parse_extension, allow_hyphen_values = true)]
+        by: u64,
+    },
+    /// Take time off the current phase (e.g. 3m, -3m)
+    Shorten {
+        #[arg(value_parser = parse_reduction, allow_hyphen_values = true)]
+        by: u64,
+    },
+    /// End the current phase now and move on to the next one
+    Skip,
+    /// Start the next phase of a `--manual-advance` cycle once the current one is over
+    #[command(visible_alias = "ack")]
+    Next,
+    /// Show a live countdown of the current timer (Ctrl-C detaches)
+    Watch,
+    /// Print the timer for a status bar
+    Bar {
         #[arg(long, value_enum)]
-        mode: Mode,
-        #[arg(long)]
-        ready_fd: Option<i32>,
-        #[arg(long)]
-        sets: Option<u64>,
-        #[arg(long)]
-        set: Option<u64>,
-        #[arg(long = "work-minutes")]
-        work_minutes: Option<u64>,
-        #[arg(long = "break-minutes")]
-        break_minutes: Option<u64>,
-        #[arg(long)]
-        start_ts: i64,
-        #[arg(long)]
-        end_ts: i64,
+        protocol: bar::Protocol,
+        /// Keep running and print a new line whenever the output changes
         #[arg(long)]
-        minutes: u64,
+        watch: bool,
+    },
+    /// Start a work session, pause/resume the current one, or start a scheduled or waiting one now
+    /// (for bar click handlers)
+    Toggle,
+    /// Print a short timer segment for shell prompts (nothing when idle)
+    Prompt {
+        /// Template with {icon}, {mode}, {remaining}, {set} and {task}
+        #[arg(long, default_value = prompt::DEFAULT_FORMAT)]
+        format: String,
+        #[command(subcommand)]
+        command: Option<PromptCommand>,
+    },
+    /// Inspect the session log
+    Log {
+        #[command(subcommand)]
+        command: LogCommand,
+    },
+    /// Set or show the daily pomodoro goal
+    Goal {
+        #[command(subcommand)]
+        command: GoalCommand,
+    },
+    /// Show how many days in a row the daily goal was met
+    Streak,
+    /// Show how much of each project's time budget is used, or set one
+    Budget {
+        #[command(subcommand)]
+        command: Option<BudgetCommand>,
+    },
+    /// Read, change or check the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    #[command(name = "__run", hide = true)]
+    Run(RunArgs),
+}
+
+#[derive(Args, Debug)]
+struct CycleArgs {
+    /// Number of work sessions (auto alternates work/break)
+    #[arg(long, value_parser = parse_sets)]
+    sets: Option<u64>,
+    /// Break length, e.g. 5m or 90s (used in a cycle of sets)
+    #[arg(long = "break-minutes", value_parser = parse_duration)]
+    break_secs: Option<u64>,
+    /// Long break length (used in a cycle of sets; default: 15m)
+    #[arg(long = "long-break-minutes", value_parser = parse_duration)]
+    long_break_secs: Option<u64>,
+    /// Take a long break after every N work sessions (used in a cycle of sets; default: 4)
+    #[arg(long = "long-break-every")]
+    long_break_every: Option<u64>,
+    /// Finish the cycle with a long break (used in a cycle of sets)
+    #[arg(long = "final-long-break")]
+    final_long_break: bool,
+    /// Wait for `tock next` at the end of each phase instead of starting the next one (used in a
+    /// cycle of sets)
+    #[arg(long)]
+    manual_advance: bool,
+}
+
+impl CycleArgs {
+    /// The settings given on the command line, for `config::session_settings`
+    /// to fill in.
+    fn settings(self, work_secs: Option<u64>) -> SessionConfig {
+        SessionConfig {
+            work_secs,
+            break_secs: self.break_secs,
+            long_break_secs: self.long_break_secs,
+            long_break_every: self.long_break_every,
+            sets: self.sets,
+            final_long_break: self.final_long_break.then_some(true),
+            manual_advance: self.manual_advance.then_some(true),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct ScheduleArgs {
+    /// Start at a local time instead of now, e.g. 14:00 or "2026-10-18 09:30"
+    #[arg(long, value_parser = parse_start_time, conflicts_with = "start_in")]
+    at: Option<i64>,
+    /// Start after a delay instead of now, e.g. 10m
+    #[arg(long = "in", value_parser = parse_duration)]
+    start_in: Option<u64>,
+}
+
+impl ScheduleArgs {
+    /// When the session should start; `None` is now.
+    fn starts_at(&self) -> Result<Option<i64>, String> {
+        match (self.at, self.start_in) {
+            (Some(at), _) => Ok(Some(at)),
+            (None, Some(secs)) => now_unix()
+                .checked_add(secs as i64)
+                .map(Some)
+                .ok_or_else(|| "timestamp overflow".to_string()),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    #[arg(long, value_enum)]
+    mode: Mode,
+    /// Written to once the daemon takes requests
+    #[arg(long)]
+    ready_fd: Option<i32>,
+    /// The steps to run, as `tock run-sequence` takes them
+    #[arg(long, requires = "step", value_parser = Sequence::parse)]
+    sequence: Option<Sequence>,
+    /// The step `--mode` is, counting from 1
+    #[arg(long, requires = "sequence")]
+    step: Option<usize>,
+    /// Wait for `tock next` between the steps
+    #[arg(long, requires = "sequence")]
+    manual_advance: bool,
+    #[arg(long)]
+    start_ts: i64,
+    #[arg(long)]
+    end_ts: i64,
+    #[arg(long = "minutes", value_parser = parse_duration)]
+    secs: u64,
+    /// The phase waits for `--start-ts` before it begins
+    #[arg(long)]
+    scheduled: bool,
+    /// The `tock box` that ends the session
+    #[arg(long)]
+    box_pid: Option<i32>,
+    /// Terminal the timer was started from, for the bell notifier.
+    #[arg(long)]
+    tty: Option<PathBuf>,
+    /// Random token identifying this daemon's session
+    #[arg(long)]
+    token: Option<String>,
+    #[command(flatten)]
+    meta: Meta,
+}
+
+#[derive(Subcommand, Debug)]
+enum PromptCommand {
+    /// Print shell code that adds the segment to your prompt
+    Init {
+        #[arg(value_enum)]
+        shell: prompt::Shell,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LogCommand {
+    /// Print the log file location
+    Path,
+    /// Show the most recent sessions
+    Tail {
+        /// Number of sessions to show
+        #[arg(long, default_value_t = 20)]
+        n: usize,
     },
+    /// Summarize today's sessions
+    Today,
+    /// Summarize this week's sessions (Monday to Sunday)
+    Week,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq, ValueEnum)]
-#[serde(rename_all = "lowercase")]
+#[serde(rename_all = "snake_case")]
 enum Mode {
     Work,
     Break,
+    #[value(name = "long_break")]
+    LongBreak,
+}
+
+impl Mode {
+    /// Stable name used in the daemon arguments and the session log.
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Work => "work",
+            Mode::Break => "break",
+            Mode::LongBreak => "long_break",
+        }
+    }
 }
 
 impl std::fmt::Display for Mode {
@@ -79,88 +318,483 @@ impl std::fmt::Display for Mode {
         match self {
             Mode::Work => write!(f, "work"),
             Mode::Break => write!(f, "break"),
+            Mode::LongBreak => write!(f, "long break"),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "StateRecord")]
 struct State {
     pid: i32,
     mode: Mode,
     start_ts: i64,
     end_ts: i64,
-    minutes: u64,
-    #[serde(default)]
-    cycle: Option<Cycle>,
+    /// Planned length of the phase.
+    secs: u64,
+    /// The phases of a cycle or `run-sequence`, positioned at this one.
+    sequence: Option<Sequence>,
+    #[serde(flatten)]
+    meta: Meta,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paused: Option<Pause>,
+    /// Seconds this phase has spent paused, not counting a pause still in progress.
+    paused_secs: u64,
+    /// The phase hasn't begun yet; it is due at `start_ts` and `end_ts` is only
+    /// planned.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    scheduled: bool,
+    /// The phase is over, at `end_ts`, and the next one waits for `tock next`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    awaiting_ack: bool,
+    /// The `tock box` running a command for this session. It ends the session
+    /// when the command exits, so the daemon waits for it past `end_ts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    box_pid: Option<i32>,
+    /// Tells the daemon apart from a process that reused its pid. Missing in
+    /// state written by older versions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identity: Option<Identity>,
 }
 
+/// A pause in progress. While paused `end_ts` is meaningless; resuming sets a
+/// new one from `remaining_secs`.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
-struct Cycle {
+struct Pause {
+    since: i64,
+    remaining_secs: u64,
+}
+
+impl State {
+    fn remaining_secs(&self, now: i64) -> u64 {
+        match self.paused {
+            Some(pause) => pause.remaining_secs,
+            None if self.scheduled => self.secs,
+            None => (self.end_ts - now).max(0) as u64,
+        }
+    }
+
+    /// Seconds until a scheduled phase begins; `None` once it has.
+    fn starts_in(&self, now: i64) -> Option<u64> {
+        self.scheduled.then(|| (self.start_ts - now).max(0) as u64)
+    }
+
+    /// What a countdown shows: the time until a scheduled phase begins, or
+    /// else the time left in it.
+    fn countdown_secs(&self, now: i64) -> u64 {
+        self.starts_in(now)
+            .unwrap_or_else(|| self.remaining_secs(now))
+    }
+
+    /// How long ago a phase waiting for `tock next` ran out; `None` unless it is waiting.
+    fn overdue_secs(&self, now: i64) -> Option<u64> {
+        self.awaiting_ack.then(|| (now - self.end_ts).max(0) as u64)
+    }
+
+    /// When the daemon next has to act on its own: at the start of a scheduled
+    /// phase or the end of a running one. `None` while paused or waiting for
+    /// `tock next`.
+    fn deadline(&self) -> Option<i64> {
+        match self.paused {
+            Some(_) => None,
+            None if self.scheduled => Some(self.start_ts),
+            None if self.awaiting_ack => None,
+            None => Some(self.end_ts),
+        }
+    }
+
+    /// Share of the phase's active time already done, from 0 to 1.
+    fn progress(&self, now: i64) -> f64 {
+        let active_until = self.paused.map_or(now, |pause| pause.since);
+        let done_secs = (active_until - self.start_ts - self.paused_secs as i64).max(0) as u64;
+        let total_secs = done_secs + self.remaining_secs(now);
+        if total_secs == 0 {
+            1.0
+        } else {
+            done_secs as f64 / total_secs as f64
+        }
+    }
+
+    /// Total paused time of this phase, including a pause that is still going on.
+    fn paused_secs_at(&self, now: i64) -> u64 {
+        let current = self
+            .paused
+            .map(|pause| (now - pause.since).max(0) as u64)
+            .unwrap_or(0);
+        self.paused_secs + current
+    }
+}
+
+/// What a session is for. Carried through every phase of a cycle and into the log.
+#[derive(Args, Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct Meta {
+    /// Task this session is for
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    task: Option<String>,
+    /// Comma-separated tags
+    #[arg(long, value_delimiter = ',')]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// Free-form note
+    #[arg(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+impl Meta {
+    /// Drops blank values so `--task ""` or `--tags a,,b` don't leave empty entries behind.
+    fn normalized(self) -> Meta {
+        let non_blank = |s: String| {
+            let trimmed = s.trim();
+            (!trimmed.is_empty()).then(|| trimmed.to_string())
+        };
+        Meta {
+            task: self.task.and_then(non_blank),
+            tags: self.tags.into_iter().filter_map(non_blank).collect(),
+            note: self.note.and_then(non_blank),
+        }
+    }
+
+    fn daemon_args(&self) -> Vec<String> {
+        // `--flag=value` keeps values that start with `-` from being read as flags.
+        let mut args = Vec::new();
+        if let Some(task) = &self.task {
+            args.push(format!("--task={task}"));
+        }
+        if !self.tags.is_empty() {
+            args.push(format!("--tags={}", self.tags.join(",")));
+        }
+        if let Some(note) = &self.note {
+            args.push(format!("--note={note}"));
+        }
+        args
+    }
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "LongBreakRecord")]
+struct LongBreak {
+    secs: u64,
+    /// A long break replaces the regular one after every `every`-th work session.
+    every: u64,
+    /// Whether the cycle ends with a long break after the last work session.
+    at_end: bool,
+}
+
+// State written before lengths were kept in seconds has whole minutes in
+// `minutes`, `work_minutes` and `break_minutes` instead, and state written
+// before sequences has a `cycle` of identical work/break pairs. These records
+// read either, so a timer started by an older version survives an upgrade.
+
+#[derive(Deserialize)]
+struct StateRecord {
+    pid: i32,
+    mode: Mode,
+    start_ts: i64,
+    end_ts: i64,
+    secs: Option<u64>,
+    minutes: Option<u64>,
+    #[serde(default)]
+    sequence: Option<Sequence>,
+    #[serde(default)]
+    cycle: Option<CycleRecord>,
+    #[serde(flatten)]
+    meta: Meta,
+    #[serde(default)]
+    paused: Option<Pause>,
+    #[serde(default)]
+    paused_secs: u64,
+    #[serde(default)]
+    scheduled: bool,
+    #[serde(default)]
+    awaiting_ack: bool,
+    #[serde(default)]
+    box_pid: Option<i32>,
+    #[serde(default)]
+    identity: Option<Identity>,
+}
+
+impl TryFrom<StateRecord> for State {
+    type Error = String;
+
+    fn try_from(record: StateRecord) -> Result<State, String> {
+        let sequence = match (record.sequence, record.cycle) {
+            (Some(sequence), _) => Some(sequence),
+            (None, Some(cycle)) => Some(cycle.into_sequence(record.mode)?),
+            (None, None) => None,
+        };
+        Ok(State {
+            pid: record.pid,
+            mode: record.mode,
+            start_ts: record.start_ts,
+            end_ts: record.end_ts,
+            secs: length_secs(record.secs, record.minutes, "secs")?,
+            sequence,
+            meta: record.meta,
+            paused: record.paused,
+            paused_secs: record.paused_secs,
+            scheduled: record.scheduled,
+            awaiting_ack: record.awaiting_ack,
+            box_pid: record.box_pid,
+            identity: record.identity,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct CycleRecord {
     set: u64,
     sets: u64,
-    work_minutes: u64,
-    break_minutes: u64,
+    work_secs: Option<u64>,
+    work_minutes: Option<u64>,
+    break_secs: Option<u64>,
+    break_minutes: Option<u64>,
+    #[serde(default)]
+    long_break: Option<LongBreak>,
+}
+
+impl CycleRecord {
+    /// The cycle's phases, positioned at set `set` in `mode`: its work session
+    /// or the break after it.
+    fn into_sequence(self, mode: Mode) -> Result<Sequence, String> {
+        let work_secs = length_secs(self.work_secs, self.work_minutes, "work_secs")?;
+        let break_secs = length_secs(self.break_secs, self.break_minutes, "break_secs")?;
+        let sequence = Sequence::cycle(self.sets, work_secs, break_secs, self.long_break);
+        // Each set but the last is a work session and a break.
+        let work_step = (self.set.max(1) as usize - 1) * 2 + 1;
+        match mode {
+            Mode::Work => sequence.at_step(work_step),
+            Mode::Break | Mode::LongBreak => sequence.at_step(work_step + 1),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LongBreakRecord {
+    secs: Option<u64>,
+    minutes: Option<u64>,
+    every: u64,
+    #[serde(default)]
+    at_end: bool,
+}
+
+impl TryFrom<LongBreakRecord> for LongBreak {
+    type Error = String;
+
+    fn try_from(record: LongBreakRecord) -> Result<LongBreak, String> {
+        Ok(LongBreak {
+            secs: length_secs(record.secs, record.minutes, "secs")?,
+            every: record.every,
+            at_end: record.at_end,
+        })
+    }
+}
+
+fn length_secs(secs: Option<u64>, minutes: Option<u64>, field: &str) -> Result<u64, String> {
+    secs.or_else(|| minutes.map(|minutes| minutes.saturating_mul(60)))
+        .ok_or_else(|| format!("missing field `{field}`"))
 }
 
 fn main() {
+    // `tock prompt` runs on every shell prompt, so it skips clap when it can.
+    if let Some(format) = prompt::fast_path_format(std::env::args_os()) {
+        prompt::print(&format);
+        return;
+    }
+
     let cli = Cli::parse();
     let exit_code = match cli.command {
         Commands::Start {
-            minutes,
-            sets,
-            break_minutes,
+            length,
+            profile,
+            cycle,
+            schedule,
+            meta,
+            output,
         } => {
-            if let Err(e) = start_work(minutes, sets, break_minutes) {
+            let started = schedule.starts_at().and_then(|starts_at| {
+                let cli = cycle.settings(length);
+                start_work(
+                    cli,
+                    profile.as_deref(),
+                    meta.normalized(),
+                    starts_at,
+                    output.format(),
+                )
+            });
+            if let Err(e) = started {
                 eprintln!("{e}");
                 2
             } else {
                 0
             }
         }
-        Commands::Break { minutes } => {
-            if let Err(e) = start_single_session(Mode::Break, minutes) {
+        Commands::RunSequence(args) => {
+            if let Err(e) = sequence::run_sequence(args) {
                 eprintln!("{e}");
                 2
             } else {
                 0
             }
         }
-        Commands::Status => match status() {
+        Commands::Timebox(args) => match timebox::timebox(args) {
             Ok(code) => code,
             Err(e) => {
                 eprintln!("{e}");
                 2
             }
         },
-        Commands::Stop => match stop() {
+        Commands::Break {
+            length,
+            profile,
+            meta,
+            output,
+        } => {
+            let cli = SessionConfig {
+                break_secs: length,
+                ..SessionConfig::default()
+            };
+            let started = config::session_settings(cli, profile.as_deref()).and_then(|settings| {
+                let meta = meta.normalized();
+                start_single_session(
+                    Mode::Break,
+                    settings.break_secs,
+                    meta,
+                    None,
+                    output.format(),
+                )
+            });
+            if let Err(e) = started {
+                eprintln!("{e}");
+                2
+            } else {
+                0
+            }
+        }
+        Commands::Status { output } => match status(output.format()) {
             Ok(code) => code,
             Err(e) => {
                 eprintln!("{e}");
                 2
             }
         },
-        Commands::Run {
-            mode,
-            ready_fd,
-            sets,
-            set,
-            work_minutes,
-            break_minutes,
-            start_ts,
-            end_ts,
-            minutes,
-        } => {
-            if let Err(e) = run_daemon(
-                mode,
-                ready_fd,
-                sets,
-                set,
-                work_minutes,
-                break_minutes,
-                start_ts,
-                end_ts,
-                minutes,
-            ) {
+        Commands::Stop { output } => match stop(output.format()) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Pause => match pause() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Resume => match resume() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Extend { by } => match extend(by) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Shorten { by } => match shorten(by) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Skip => match skip() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Next => match next() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Watch => match watch::watch() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Bar { protocol, watch } => {
+            match state_paths().and_then(|(state_path, _)| bar::bar(protocol, watch, &state_path)) {
+                Ok(code) => code,
+                Err(e) => {
+                    eprintln!("{e}");
+                    2
+                }
+            }
+        }
+        Commands::Toggle => match toggle() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Prompt { format, command } => {
+            match command {
+                Some(PromptCommand::Init { shell }) => print!("{0}", prompt::init_script(shell)),
+                None => prompt::print(&format),
+            }
+            0
+        }
+        Commands::Log { command } => match log_command(command) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Goal { command } => match goal::goal_command(command) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Streak => match goal::streak() {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Budget { command } => match budget::budget_command(command) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Config { command } => match config::config_command(command) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{e}");
+                2
+            }
+        },
+        Commands::Run(args) => {
+            if let Err(e) = run_daemon(args) {
                 eprintln!("{e}");
                 2
             } else {
@@ -172,68 +806,165 @@ fn main() {
     std::process::exit(exit_code);
 }
 
-fn start_work(minutes: Option<u64>, sets: Option<u64>, break_minutes: Option<u64>) -> Result<(), String> {
-    let Some(sets) = sets else {
-        return start_single_session(Mode::Work, minutes);
-    };
-
+/// Starts a work session, or a cycle of them, with the settings `cli` gives
+/// on the command line completed by `config::session_settings`.
+fn start_work(
+    cli: SessionConfig,
+    profile: Option<&str>,
+    meta: Meta,
+    starts_at: Option<i64>,
+    format: Format,
+) -> Result<(), String> {
+    let cycle_flags = cycle_only_flags(&cli);
+    let settings = config::session_settings(cli, profile)?;
+    let sets = settings.sets.unwrap_or(1);
     validate_sets(sets)?;
-    if sets <= 1 {
-        return start_single_session(Mode::Work, minutes);
+    let final_long_break = settings.final_long_break.unwrap_or(false);
+    // `--sets 1 --final-long-break` is a cycle of one work session and its long break.
+    if sets <= 1 && !(settings.sets.is_some() && final_long_break) {
+        // The same settings from the config file simply don't apply.
+        if let Some((last, rest)) = cycle_flags.split_last() {
+            let flags = match rest {
+                [] => format!("{last} only applies"),
+                _ => format!("{0} and {last} only apply", rest.join(", ")),
+            };
+            return Err(format!("{flags} to a cycle (add --sets N)"));
+        }
+        return start_single_session(Mode::Work, settings.work_secs, meta, starts_at, format);
     }
 
-    let work_minutes = minutes.unwrap_or(DEFAULT_WORK_MINUTES);
-    let break_minutes = break_minutes.unwrap_or(DEFAULT_BREAK_MINUTES);
-    validate_minutes(work_minutes)?;
-    validate_minutes(break_minutes)?;
+    let work_secs = settings.work_secs.unwrap_or(DEFAULT_WORK_SECS);
+    let break_secs = settings.break_secs.unwrap_or(DEFAULT_BREAK_SECS);
+    validate_length(work_secs)?;
+    validate_length(break_secs)?;
+    let long_break = parse_long_break(
+        settings.long_break_secs,
+        settings.long_break_every,
+        final_long_break,
+    )?;
+
+    let sequence = Sequence::cycle(sets, work_secs, break_secs, long_break)
+        .with_manual_advance(settings.manual_advance.unwrap_or(false));
+    start_session(
+        Mode::Work,
+        work_secs,
+        Some(sequence),
+        meta,
+        starts_at,
+        format,
+    )
+}
 
-    let cycle = Cycle {
-        set: 1,
-        sets,
-        work_minutes,
-        break_minutes,
-    };
-    start_session(Mode::Work, work_minutes, Some(cycle))
+/// The flags in `cli` that only mean something for a cycle of sets.
+fn cycle_only_flags(cli: &SessionConfig) -> Vec<&'static str> {
+    [
+        ("--break-minutes", cli.break_secs.is_some()),
+        ("--long-break-minutes", cli.long_break_secs.is_some()),
+        ("--long-break-every", cli.long_break_every.is_some()),
+        ("--final-long-break", cli.final_long_break.is_some()),
+        ("--manual-advance", cli.manual_advance.is_some()),
+    ]
+    .iter()
+    .filter(|&&(_, given)| given)
+    .map(|&(flag, _)| flag)
+    .collect()
 }
 
-fn start_single_session(mode: Mode, minutes: Option<u64>) -> Result<(), String> {
-    let minutes = minutes.unwrap_or(match mode {
-        Mode::Work => DEFAULT_WORK_MINUTES,
-        Mode::Break => DEFAULT_BREAK_MINUTES,
+fn start_single_session(
+    mode: Mode,
+    length: Option<u64>,
+    meta: Meta,
+    starts_at: Option<i64>,
+    format: Format,
+) -> Result<(), String> {
+    let secs = length.unwrap_or(match mode {
+        Mode::Work => DEFAULT_WORK_SECS,
+        Mode::Break => DEFAULT_BREAK_SECS,
+        Mode::LongBreak => DEFAULT_LONG_BREAK_SECS,
     });
-    validate_minutes(minutes)?;
-    start_session(mode, minutes, None)
+    validate_length(secs)?;
+    start_session(mode, secs, None, meta, starts_at, format)
 }
 
-fn start_session(mode: Mode, minutes: u64, cycle: Option<Cycle>) -> Result<(), String> {
-    let (state_path, legacy_state_path) = state_paths()?;
-    stop_existing(&legacy_state_path)?;
-    stop_existing(&state_path)?;
+/// Starts a daemon for the session, replacing any running timer, and says so.
+/// With `starts_at` the daemon waits until then before the phase begins.
+fn start_session(
+    mode: Mode,
+    secs: u64,
+    sequence: Option<Sequence>,
+    meta: Meta,
+    starts_at: Option<i64>,
+    format: Format,
+) -> Result<(), String> {
+    let state = spawn_session(mode, secs, sequence, meta, starts_at, None)?;
+    if format != Format::Text {
+        Report::started(&state, now_unix())?.print(format)?;
+        return Ok(());
+    }
+    let (verb, at) = if state.scheduled {
+        (
+            "scheduled",
+            format!(" at {0}", format_start_time(state.start_ts)?),
+        )
+    } else {
+        ("started", String::new())
+    };
+    let pid = state.pid;
+    match &state.sequence {
+        Some(sequence) => println!("{verb} sequence: {0}{at} (pid {pid})", sequence.summary()),
+        None => println!(
+            "{verb} {mode} timer for {0}{at} (pid {pid})",
+            format_length(secs)
+        ),
+    }
+    Ok(())
+}
 
-    let start_ts = now_unix();
+/// Starts a daemon for the session, replacing any running timer, and returns
+/// the timer it runs. `box_pid` is the `tock box` that ends the session.
+fn spawn_session(
+    mode: Mode,
+    secs: u64,
+    sequence: Option<Sequence>,
+    meta: Meta,
+    starts_at: Option<i64>,
+    box_pid: Option<i32>,
+) -> Result<State, String> {
+    let (state_path, legacy_state_path) = state_paths()?;
+    // Held until the new daemon has written its state, so a concurrent start
+    // either replaces this timer or is replaced by it.
+    let lock = StateLock::acquire(&state_path)?;
+    stop_timer(&legacy_state_path, EndReason::ReplacedByNewTimer)?;
+    stop_timer(&state_path, EndReason::ReplacedByNewTimer)?;
+
+    let scheduled = starts_at.is_some();
+    let start_ts = starts_at.unwrap_or_else(now_unix);
     let end_ts = start_ts
-        .checked_add((minutes as i64).saturating_mul(60))
+        .checked_add(secs as i64)
         .ok_or_else(|| "timestamp overflow".to_string())?;
 
+    let token = identity::new_token()?;
+    // The daemon writes a byte here once it is listening for requests.
     let (ready_read_fd, ready_write_fd) = create_pipe()?;
 
     let exe = std::env::current_exe().map_err(|e| format!("failed to resolve current executable: {e}"))?;
     let mut cmd = Command::new(exe);
     cmd.arg("__run")
         .arg("--mode")
-        .arg(mode.to_string())
+        .arg(mode.as_str())
         .arg("--ready-fd")
-        .arg(ready_read_fd.to_string());
-
-    if let Some(cycle) = cycle {
-        cmd.arg("--sets")
-            .arg(cycle.sets.to_string())
-            .arg("--set")
-            .arg(cycle.set.to_string())
-            .arg("--work-minutes")
-            .arg(cycle.work_minutes.to_string())
-            .arg("--break-minutes")
-            .arg(cycle.break_minutes.to_string());
+        .arg(ready_write_fd.to_string())
+        // One argument, so the token can be found on the daemon's command line.
+        .arg(format!("--token={token}"));
+
+    if let Some(sequence) = &sequence {
+        cmd.arg("--sequence")
+            .arg(sequence.to_string())
+            .arg("--step")
+            .arg(sequence.step().to_string());
+        if sequence.manual_advance() {
+            cmd.arg("--manual-advance");
+        }
     }
 
     cmd.arg("--start-ts")
@@ -241,15 +972,25 @@ fn start_session(mode: Mode, minutes: u64, cycle: Option<Cycle>) -> Result<(), S
         .arg("--end-ts")
         .arg(end_ts.to_string())
         .arg("--minutes")
-        .arg(minutes.to_string())
-        .stdin(Stdio::null())
+        .arg(format!("{secs}s"))
+        .args(meta.daemon_args());
+    if scheduled {
+        cmd.arg("--scheduled");
+    }
+    if let Some(box_pid) = box_pid {
+        cmd.arg("--box-pid").arg(box_pid.to_string());
+    }
+    if let Some(tty) = controlling_tty() {
+        cmd.arg("--tty").arg(tty);
+    }
+    cmd.stdin(Stdio::null())
         .stdout(Stdio::null())
-        .stderr(Stdio::null());
+        .stderr(open_daemon_log());
 
     unsafe {
         cmd.pre_exec(move || {
-            // Ensure only the parent holds the write-end, so the daemon can block on EOF.
-            libc::close(ready_write_fd);
+            // Only the daemon holds the write end, so its exit shows up as EOF.
+            libc::close(ready_read_fd);
             if libc::setsid() == -1 {
                 return Err(io::Error::last_os_error());
             }
@@ -268,260 +1009,553 @@ fn start_session(mode: Mode, minutes: u64, cycle: Option<Cycle>) -> Result<(), S
         }
     };
     let pid = child.id() as i32;
+    unsafe {
+        libc::close(ready_write_fd);
+    }
+    if !wait_for_daemon(ready_read_fd) {
+        let log = daemon_log_path()
+            .map(|path| format!("; see {}", path.display()))
+            .unwrap_or_default();
+        return Err(format!("background process failed to start{log}"));
+    }
+    drop(lock);
 
-    let state = State {
+    Ok(State {
         pid,
         mode,
         start_ts,
         end_ts,
-        minutes,
-        cycle,
+        secs,
+        sequence,
+        meta,
+        paused: None,
+        paused_secs: 0,
+        scheduled,
+        awaiting_ack: false,
+        box_pid,
+        identity: Identity::of(pid, &token),
+    })
+}
+
+fn status(format: Format) -> Result<i32, String> {
+    // The daemon knows best; state.json is the fallback if it doesn't answer.
+    let state = current_state()?.map(|(_, snapshot)| {
+        control_socket_path(snapshot.pid)
+            .and_then(|path| control::request(&path, &Request::Status))
+            .ok()
+            .and_then(|response| response.state)
+            .unwrap_or(snapshot)
+    });
+    let now = now_unix();
+    // The goal is extra; a broken config or log shouldn't stop `status`.
+    let goal = goal::Progress::load(now).unwrap_or_else(|e| {
+        eprintln!("warning: {e}");
+        None
+    });
+    let code = match &state {
+        Some(state) if state.paused.is_some() => 3,
+        Some(state) if state.scheduled => 4,
+        Some(state) if state.awaiting_ack => 5,
+        Some(_) => 0,
+        None => 1,
     };
-    if let Err(e) = write_state(&state_path, &state) {
-        let _ = send_sigterm(pid);
-        unsafe {
-            libc::close(ready_read_fd);
-            libc::close(ready_write_fd);
-        }
-        return Err(e);
+    if format != Format::Text {
+        Report::status(state.as_ref(), now)?
+            .with_goal(goal)
+            .print(format)?;
+        return Ok(code);
     }
 
-    unsafe {
-        libc::close(ready_read_fd);
-        libc::close(ready_write_fd);
+    let Some(state) = state else {
+        println!("not running");
+        if let Some(goal) = goal {
+            println!("pomodoros today: {goal}");
+        }
+        return Ok(code);
+    };
+    let remaining_secs = state.remaining_secs(now);
+
+    let run_state = if state.scheduled {
+        "scheduled"
+    } else if state.paused.is_some() {
+        "paused"
+    } else if state.awaiting_ack {
+        "awaiting_ack"
+    } else {
+        "running"
+    };
+    println!("{run_state}");
+    println!("mode: {0}", state.mode);
+    println!("pid: {0}", state.pid);
+    if let Some(sequence) = &state.sequence {
+        println!("set: {0}/{1}", sequence.set(), sequence.sets());
+        println!("step: {0}/{1}", sequence.step(), sequence.steps());
+        if let Some(next) = sequence.next() {
+            println!("next: {0} {1}", next.mode, format_length(next.secs));
+        }
     }
-
-    match state.cycle {
-        Some(cycle) => println!(
-            "started cycle: work {}m / break {}m x{} (pid {})",
-            cycle.work_minutes, cycle.break_minutes, cycle.sets, pid
-        ),
-        None => println!("started {mode} timer for {minutes} minutes (pid {pid})"),
+    if let Some(starts_in) = state.starts_in(now) {
+        println!("starts_at: {0}", format_local_time(state.start_ts)?);
+        println!("starts_in: {0}", format_duration(starts_in));
+        println!("length: {0}", format_length(state.secs));
+    } else {
+        println!("started_at: {0}", format_local_time(state.start_ts)?);
+        if let Some(overdue_secs) = state.overdue_secs(now) {
+            println!("ended_at: {0}", format_local_time(state.end_ts)?);
+            println!(
+                "overdue: {0} (`tock next` starts the next phase)",
+                format_duration(overdue_secs)
+            );
+        } else {
+            match state.paused {
+                Some(pause) => println!("paused_at: {0}", format_local_time(pause.since)?),
+                None => println!("ends_at: {0}", format_local_time(state.end_ts)?),
+            }
+            println!("remaining: {0}", format_duration(remaining_secs));
+        }
     }
-    Ok(())
+    if let Some(task) = &state.meta.task {
+        println!("task: {task}");
+    }
+    if !state.meta.tags.is_empty() {
+        println!("tags: {0}", state.meta.tags.join(", "));
+    }
+    if let Some(note) = &state.meta.note {
+        println!("note: {note}");
+    }
+    if let Some(goal) = goal {
+        println!("pomodoros today: {goal}");
+    }
+    match budget::status_lines(&state, now) {
+        Ok(lines) => lines.iter().for_each(|line| println!("{line}")),
+        Err(e) => eprintln!("warning: {e}"),
+    }
+    Ok(code)
 }
 
-fn status() -> Result<i32, String> {
+/// Finds the live session, checking the legacy location too. State left behind
+/// by a daemon that is gone is cleared along the way.
+fn current_state() -> Result<Option<(PathBuf, State)>, String> {
     let (primary_state_path, legacy_state_path) = state_paths()?;
-    let (state_path, state) = match read_state(&primary_state_path)? {
-        Some(state) => (primary_state_path, state),
-        None => match read_state(&legacy_state_path)? {
-            Some(state) => (legacy_state_path, state),
-            None => {
-                println!("not running");
-                return Ok(1);
+    for state_path in [&primary_state_path, &legacy_state_path] {
+        let Some(state) = read_state(state_path)? else {
+            continue;
+        };
+        if identity::daemon_running(&state) {
+            return Ok(Some((state_path.clone(), state)));
+        }
+        // A new timer may have been started since the read; only clear the
+        // state if it is still stale with the lock held.
+        let _lock = StateLock::acquire(&primary_state_path)?;
+        if let Some(state) = read_state(state_path)? {
+            if identity::daemon_running(&state) {
+                return Ok(Some((state_path.clone(), state)));
             }
-        },
-    };
+            clear_state(state_path)?;
+        }
+    }
+    Ok(None)
+}
 
-    if !pid_alive(state.pid)? {
-        clear_state(&state_path)?;
-        println!("not running");
-        return Ok(1);
+/// Starts work as a bare `tock start` would when idle, a scheduled session
+/// right away and the next phase of one waiting for `tock next`, otherwise
+/// pauses or resumes.
+fn toggle() -> Result<i32, String> {
+    match current_state()? {
+        None => {
+            start_work(
+                SessionConfig::default(),
+                None,
+                Meta::default(),
+                None,
+                Format::Text,
+            )?;
+            Ok(0)
+        }
+        Some((_, state)) if state.scheduled => skip(),
+        Some((_, state)) if state.awaiting_ack => next(),
+        Some((_, state)) if state.paused.is_some() => resume(),
+        Some(_) => pause(),
     }
+}
 
-    let now = now_unix();
-    let remaining_secs = (state.end_ts - now).max(0) as u64;
+fn pause() -> Result<i32, String> {
+    let response = match control_request(&Request::Pause)? {
+        Ok(response) => response,
+        Err(code) => return Ok(code),
+    };
+    let state = live_state(response)?;
+    println!(
+        "paused {0} ({1} remaining)",
+        state.mode,
+        format_duration(state.remaining_secs(now_unix()))
+    );
+    Ok(0)
+}
 
-    println!("running");
-    println!("mode: {0}", state.mode);
-    println!("pid: {0}", state.pid);
-    if let Some(cycle) = state.cycle {
-        println!("set: {0}/{1}", cycle.set, cycle.sets);
+fn resume() -> Result<i32, String> {
+    let response = match control_request(&Request::Resume)? {
+        Ok(response) => response,
+        Err(code) => return Ok(code),
+    };
+    let state = live_state(response)?;
+    println!(
+        "resumed {0} (ends at {1})",
+        state.mode,
+        format_local_time(state.end_ts)?
+    );
+    Ok(0)
+}
+
+fn extend(secs: u64) -> Result<i32, String> {
+    let response = match control_request(&Request::Extend { secs })? {
+        Ok(response) => response,
+        Err(code) => return Ok(code),
+    };
+    let state = live_state(response)?;
+    println!(
+        "extended {0} by {1} ({2} remaining)",
+        state.mode,
+        format_duration(secs),
+        format_duration(state.remaining_secs(now_unix()))
+    );
+    Ok(0)
+}
+
+fn shorten(secs: u64) -> Result<i32, String> {
+    let response = match control_request(&Request::Shorten { secs })? {
+        Ok(response) => response,
+        Err(code) => return Ok(code),
+    };
+    let state = live_state(response)?;
+    println!(
+        "shortened {0} by {1} ({2} remaining)",
+        state.mode,
+        format_duration(secs),
+        format_duration(state.remaining_secs(now_unix()))
+    );
+    Ok(0)
+}
+
+fn skip() -> Result<i32, String> {
+    advance(&Request::Skip)
+}
+
+fn next() -> Result<i32, String> {
+    advance(&Request::Next)
+}
+
+/// Sends `skip` or `next` and says which phase ended and which one starts.
+fn advance(request: &Request) -> Result<i32, String> {
+    let response = match control_request(request)? {
+        Ok(response) => response,
+        Err(code) => return Ok(code),
+    };
+    let Some(ended) = response.ended else {
+        // Skipping the wait for a scheduled timer starts it early.
+        let state = live_state(response)?;
+        println!("{0} starts now", state.mode);
+        return Ok(0);
+    };
+    // A phase waiting for `tock next` had run its course.
+    let verb = if ended.awaiting_ack {
+        "finished"
+    } else {
+        "skipped"
+    };
+    match response
+        .state
+        .and_then(|next| Some((next.mode, next.sequence?)))
+    {
+        Some((next_mode, sequence)) => println!(
+            "{verb} {0}; {1} (step {2}/{3}) starts now",
+            ended.mode,
+            next_mode,
+            sequence.step(),
+            sequence.steps()
+        ),
+        None => println!("{verb} {0}", ended.mode),
     }
-    println!("started_at: {0}", format_local_time(state.start_ts)?);
-    println!("ends_at: {0}", format_local_time(state.end_ts)?);
-    println!("remaining: {0}", format_duration(remaining_secs));
     Ok(0)
 }
 
-fn stop() -> Result<i32, String> {
-    let (primary_state_path, legacy_state_path) = state_paths()?;
-    let mut stopped = false;
+/// Sends `request` to the running timer. `Err(code)` means it didn't apply and
+/// the reason has been printed: `not running`, or a refusal such as `already paused`.
+fn control_request(request: &Request) -> Result<Result<Response, i32>, String> {
+    let Some((_, state)) = current_state()? else {
+        println!("not running");
+        return Ok(Err(1));
+    };
+    let response = control::request(&control_socket_path(state.pid)?, request)?;
+    if let Some(e) = response.error {
+        return Err(e);
+    }
+    if let Some(reason) = response.refused {
+        println!("{reason}");
+        return Ok(Err(1));
+    }
+    Ok(Ok(response))
+}
+
+fn live_state(response: Response) -> Result<State, String> {
+    response
+        .state
+        .ok_or_else(|| "the timer stopped while handling the request".to_string())
+}
 
+fn stop(format: Format) -> Result<i32, String> {
+    let (primary_state_path, legacy_state_path) = state_paths()?;
+    let lock = StateLock::acquire(&primary_state_path)?;
+    let mut stopped = Vec::new();
     for state_path in [&primary_state_path, &legacy_state_path] {
-        let Some(state) = read_state(state_path)? else {
-            continue;
-        };
+        stopped.extend(stop_timer(state_path, EndReason::Stopped)?);
+    }
+    drop(lock);
+    for timer in &stopped {
+        if timer.run_hook {
+            run_hook(Hook::Stopped, &timer.state, Some(EndReason::Stopped));
+        }
+    }
+    let stopped = stopped.into_iter().next().map(|timer| timer.state);
+
+    let code = if stopped.is_some() { 0 } else { 1 };
+    if format != Format::Text {
+        Report::stopped(stopped.as_ref(), now_unix())?.print(format)?;
+        return Ok(code);
+    }
+    match stopped {
+        Some(state) if state.scheduled => println!("cancelled the scheduled {0}", state.mode),
+        Some(_) => println!("stopped"),
+        None => println!("not running"),
+    }
+    Ok(code)
+}
 
-        if pid_alive(state.pid)? {
-            send_sigterm(state.pid)?;
-            stopped = true;
+fn log_command(command: LogCommand) -> Result<i32, String> {
+    let path = log_path()?;
+    match command {
+        LogCommand::Path => println!("{}", path.display()),
+        LogCommand::Tail { n } => {
+            let entries = log::read_entries(&path)?;
+            for entry in &entries[entries.len().saturating_sub(n)..] {
+                let set = match (entry.set, entry.sets) {
+                    (Some(set), Some(sets)) => format!("  set {set}/{sets}"),
+                    _ => String::new(),
+                };
+                let task = match &entry.meta.task {
+                    Some(task) => format!("  {task}"),
+                    None => String::new(),
+                };
+                let paused = match entry.paused_secs {
+                    0 => String::new(),
+                    secs => format!("  (paused {0})", format_duration(secs)),
+                };
+                let overtime = match entry.overtime_secs {
+                    0 => String::new(),
+                    secs => format!("  ({0} overtime)", format_duration(secs)),
+                };
+                let suspended = if entry.while_suspended {
+                    "  (while suspended)"
+                } else {
+                    ""
+                };
+                let exit = match entry.exit_status {
+                    Some(status) => format!("  (exit {status})"),
+                    None => String::new(),
+                };
+                println!(
+                    "{0}  {1:<10}  {2:>8}  {3}{4}{5}{6}{7}{8}{9}",
+                    format_local_time(entry.start_ts)?,
+                    entry.mode.to_string(),
+                    format_duration(entry.active_secs()),
+                    entry.end_reason.as_str(),
+                    suspended,
+                    exit,
+                    set,
+                    paused,
+                    overtime,
+                    task
+                );
+            }
+        }
+        LogCommand::Today => {
+            let entries = log::read_entries(&path)?;
+            let day_start = local_day_start(now_unix())?;
+            let day_end = add_local_days(day_start, 1)?;
+            let summary = Summary::for_range(&entries, day_start, day_end);
+            println!("date: {0}", format_local_date(day_start)?);
+            print_summary(&summary);
+        }
+        LogCommand::Week => {
+            let entries = log::read_entries(&path)?;
+            let week_start = local_week_start(now_unix())?;
+            let mut total = Summary::default();
+            for day in 0..7 {
+                let day_start = add_local_days(week_start, day)?;
+                let day_end = add_local_days(day_start, 1)?;
+                let summary = Summary::for_range(&entries, day_start, day_end);
+                println!(
+                    "{0}  work {1}  break {2}  pomodoros {3}",
+                    format_local_date(day_start)?,
+                    format_duration(summary.work_secs),
+                    format_duration(summary.break_secs),
+                    summary.pomodoros
+                );
+                total.work_secs += summary.work_secs;
+                total.break_secs += summary.break_secs;
+                total.paused_secs += summary.paused_secs;
+                total.pomodoros += summary.pomodoros;
+            }
+            println!();
+            print_summary(&total);
         }
-        clear_state(state_path)?;
     }
+    Ok(0)
+}
 
-    if stopped {
-        println!("stopped");
-        Ok(0)
-    } else {
-        println!("not running");
-        Ok(1)
-    }
+fn print_summary(summary: &Summary) {
+    println!("work: {0}", format_duration(summary.work_secs));
+    println!("break: {0}", format_duration(summary.break_secs));
+    println!("paused: {0}", format_duration(summary.paused_secs));
+    println!("pomodoros: {0}", summary.pomodoros);
 }
 
-fn run_daemon(
-    mode: Mode,
-    ready_fd: Option<i32>,
-    sets: Option<u64>,
-    set: Option<u64>,
-    work_minutes: Option<u64>,
-    break_minutes: Option<u64>,
-    start_ts: i64,
-    end_ts: i64,
-    minutes: u64,
-) -> Result<(), String> {
-    validate_minutes(minutes)?;
-    let mut cycle = parse_cycle(sets, set, work_minutes, break_minutes)?;
+fn run_daemon(args: RunArgs) -> Result<(), String> {
+    let RunArgs {
+        mode,
+        ready_fd,
+        sequence,
+        step,
+        manual_advance,
+        start_ts,
+        end_ts,
+        secs,
+        scheduled,
+        box_pid,
+        tty,
+        token,
+        meta,
+    } = args;
+    validate_length(secs)?;
+    let sequence = sequence
+        .map(|sequence| {
+            let sequence = sequence.with_manual_advance(manual_advance);
+            sequence.at_step(step.unwrap_or(1))
+        })
+        .transpose()?;
     let (state_path, _) = state_paths()?;
     let pid = std::process::id() as i32;
 
-    if let Some(fd) = ready_fd {
-        wait_for_ready_fd(fd);
-    }
-
-    let mut current_mode = mode;
-    let mut current_start_ts = start_ts;
-    let mut current_end_ts = end_ts;
-    let mut current_minutes = minutes;
-
-    loop {
-        if !state_matches(
-            &state_path,
-            pid,
-            current_mode,
-            current_start_ts,
-            current_end_ts,
-            current_minutes,
-        )? {
-            return Ok(());
-        }
-
-        let now = now_unix();
-        if current_end_ts > now {
-            std::thread::sleep(Duration::from_secs((current_end_ts - now) as u64));
-        }
-
-        if !state_matches(
-            &state_path,
-            pid,
-            current_mode,
-            current_start_ts,
-            current_end_ts,
-            current_minutes,
-        )? {
-            return Ok(());
-        }
-
-        let finished_mode = current_mode;
-
-        match cycle {
-            None => {
-                let _ = clear_state(&state_path);
-                notify(finished_mode);
-                return Ok(());
-            }
-            Some(mut cfg) => {
-                if finished_mode == Mode::Work {
-                    if cfg.set >= cfg.sets {
-                        let _ = clear_state(&state_path);
-                        notify(finished_mode);
-                        return Ok(());
-                    }
-
-                    let next_mode = Mode::Break;
-                    let next_start_ts = now_unix();
-                    let next_minutes = cfg.break_minutes;
-                    let next_end_ts = next_start_ts
-                        .checked_add((next_minutes as i64).saturating_mul(60))
-                        .ok_or_else(|| "timestamp overflow".to_string())?;
-
-                    let next_state = State {
-                        pid,
-                        mode: next_mode,
-                        start_ts: next_start_ts,
-                        end_ts: next_end_ts,
-                        minutes: next_minutes,
-                        cycle: Some(cfg),
-                    };
-
-                    write_state(&state_path, &next_state)?;
-                    current_mode = next_mode;
-                    current_start_ts = next_start_ts;
-                    current_end_ts = next_end_ts;
-                    current_minutes = next_minutes;
-                    cycle = Some(cfg);
-
-                    notify(finished_mode);
-                    continue;
-                }
-
-                // Break finished; advance to next work session.
-                if cfg.set >= cfg.sets {
-                    let _ = clear_state(&state_path);
-                    notify(finished_mode);
-                    return Ok(());
-                }
-
-                cfg.set = cfg
-                    .set
-                    .checked_add(1)
-                    .ok_or_else(|| "set counter overflow".to_string())?;
-
-                let next_mode = Mode::Work;
-                let next_start_ts = now_unix();
-                let next_minutes = cfg.work_minutes;
-                let next_end_ts = next_start_ts
-                    .checked_add((next_minutes as i64).saturating_mul(60))
-                    .ok_or_else(|| "timestamp overflow".to_string())?;
-
-                let next_state = State {
-                    pid,
-                    mode: next_mode,
-                    start_ts: next_start_ts,
-                    end_ts: next_end_ts,
-                    minutes: next_minutes,
-                    cycle: Some(cfg),
-                };
-
-                write_state(&state_path, &next_state)?;
-                current_mode = next_mode;
-                current_start_ts = next_start_ts;
-                current_end_ts = next_end_ts;
-                current_minutes = next_minutes;
-                cycle = Some(cfg);
-
-                notify(finished_mode);
-            }
-        }
-    }
+    let current = State {
+        pid,
+        mode,
+        start_ts,
+        end_ts,
+        secs,
+        sequence,
+        meta: meta.normalized(),
+        paused: None,
+        paused_secs: 0,
+        scheduled,
+        awaiting_ack: false,
+        box_pid,
+        identity: token.and_then(|token| Identity::of(pid, &token)),
+    };
+    daemon::run(
+        current,
+        &state_path,
+        &control_socket_path(pid)?,
+        ready_fd,
+        tty,
+    )
 }
 
-fn notify(mode: Mode) {
-    let (body, beeps) = match mode {
-        Mode::Work => ("Work finished. Time for a break.", 2),
-        Mode::Break => ("Break finished. Back to work.", 1),
+/// Sends a notification through the configured backends. The daemon's stderr
+/// goes to daemon.log, so that is where failures end up. The config is read on
+/// every notification so edits apply to running timers.
+fn notify(notification: &Notification, tty: Option<&Path>) {
+    let config = match config_path().and_then(|path| config::load(&path)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}; using the default notifier");
+            config::Config::default()
+        }
     };
-    let script = format!("display notification \"{body}\" with title \"Pomodoro\"");
-    let _ = Command::new("osascript").arg("-e").arg(script).status();
-
-    for _ in 0..beeps {
-        let _ = Command::new("osascript").arg("-e").arg("beep").status();
+    let notifier = notify::from_config(&config.notify, tty);
+    if let Err(e) = notifier.notify(notification) {
+        eprintln!("notification failed: {e}");
     }
 }
 
-fn state_path_for_dir(dir_name: &str) -> Result<PathBuf, String> {
+fn data_dir_for(dir_name: &str) -> Result<PathBuf, String> {
     if let Some(base) = std::env::var_os("XDG_DATA_HOME") {
         if !base.as_os_str().is_empty() {
-            return Ok(PathBuf::from(base).join(dir_name).join("state.json"));
+            return Ok(PathBuf::from(base).join(dir_name));
         }
     }
     let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set".to_string())?;
     Ok(PathBuf::from(home)
         .join(".local")
         .join("share")
-        .join(dir_name)
-        .join("state.json"))
+        .join(dir_name))
+}
+
+/// Where a daemon listens for requests. Sockets belong in `$XDG_RUNTIME_DIR`;
+/// without one they go next to the state file.
+fn control_socket_path(pid: i32) -> Result<PathBuf, String> {
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(base) if !base.is_empty() => PathBuf::from(base).join(STATE_DIR),
+        _ => data_dir_for(STATE_DIR)?,
+    };
+    Ok(dir.join(format!("daemon-{pid}.sock")))
+}
+
+fn config_dir() -> Result<PathBuf, String> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(base) if !base.is_empty() => PathBuf::from(base),
+        _ => {
+            let home = std::env::var_os("HOME").ok_or_else(|| "HOME is not set".to_string())?;
+            PathBuf::from(home).join(".config")
+        }
+    };
+    Ok(base.join(STATE_DIR))
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
+fn hooks_dir() -> Result<PathBuf, String> {
+    Ok(config_dir()?.join("hooks"))
+}
+
+fn state_path_for_dir(dir_name: &str) -> Result<PathBuf, String> {
+    Ok(data_dir_for(dir_name)?.join("state.json"))
+}
+
+fn log_path_for_dir(dir_name: &str) -> Result<PathBuf, String> {
+    Ok(data_dir_for(dir_name)?.join("log.csv"))
+}
+
+/// Where the daemon's stderr goes, so failures it can't report otherwise
+/// (notifications, logging) leave a trace.
+fn daemon_log_path() -> Result<PathBuf, String> {
+    Ok(data_dir_for(STATE_DIR)?.join("daemon.log"))
+}
+
+fn open_daemon_log() -> Stdio {
+    let file = daemon_log_path().ok().and_then(|path| {
+        fs::create_dir_all(path.parent()?).ok()?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .ok()
+    });
+    match file {
+        Some(file) => Stdio::from(file),
+        None => Stdio::null(),
+    }
 }
 
 fn state_paths() -> Result<(PathBuf, PathBuf), String> {
@@ -531,6 +1565,37 @@ fn state_paths() -> Result<(PathBuf, PathBuf), String> {
     ))
 }
 
+fn log_path() -> Result<PathBuf, String> {
+    log_path_for_dir(STATE_DIR)
+}
+
+/// Records the end of a session. Logging must never keep a timer from stopping,
+/// so failures are reported and otherwise ignored.
+fn log_session(state: &State, end_ts: i64, reason: EndReason) {
+    log_entry(&LogEntry::from_state(state, end_ts, reason));
+}
+
+fn log_entry(entry: &LogEntry) {
+    let result = log_path().and_then(|path| log::append_entry(&path, entry));
+    if let Err(e) = result {
+        eprintln!("warning: {e}");
+    }
+}
+
+/// Logs the end of a session and runs the `session_end` hook.
+fn end_session(state: &State, end_ts: i64, reason: EndReason) {
+    log_session(state, end_ts, reason);
+    run_hook(Hook::SessionEnd, state, Some(reason));
+}
+
+/// Runs a hook script. Like logging, a failing hook never affects the timer.
+fn run_hook(hook: Hook, state: &State, end_reason: Option<EndReason>) {
+    let result = hooks_dir().and_then(|dir| hooks::run(&dir, hook, state, end_reason));
+    if let Err(e) = result {
+        eprintln!("warning: {e}");
+    }
+}
+
 fn read_state(path: &Path) -> Result<Option<State>, String> {
     let contents = match fs::read_to_string(path) {
         Ok(contents) => contents,
@@ -567,50 +1632,57 @@ fn clear_state(path: &Path) -> Result<(), String> {
     }
 }
 
-fn state_matches(
-    path: &Path,
-    pid: i32,
-    mode: Mode,
-    start_ts: i64,
-    end_ts: i64,
-    minutes: u64,
-) -> Result<bool, String> {
-    let Some(state) = read_state(path)? else {
-        return Ok(false);
-    };
-    Ok(state.pid == pid
-        && state.mode == mode
-        && state.start_ts == start_ts
-        && state.end_ts == end_ts
-        && state.minutes == minutes)
+/// A timer stopped by `stop_timer`.
+struct Stopped {
+    /// The phase it was in.
+    state: State,
+    /// Whether the `stopped` hook is left for the caller to run, once it has
+    /// dropped the `StateLock`.
+    run_hook: bool,
 }
 
-fn stop_existing(state_path: &Path) -> Result<(), String> {
+/// Stops the timer whose state is at `state_path`, if it is still running.
+/// The caller holds the `StateLock`. A daemon that doesn't answer on its
+/// control socket (one started by an older version, say) is terminated
+/// instead, and its session logged from here.
+fn stop_timer(state_path: &Path, reason: EndReason) -> Result<Option<Stopped>, String> {
     let Some(state) = read_state(state_path)? else {
-        return Ok(());
+        return Ok(None);
     };
-
-    if pid_alive(state.pid)? {
-        send_sigterm(state.pid)?;
+    if !identity::daemon_running(&state) {
+        clear_state(state_path)?;
+        return Ok(None);
     }
-    clear_state(state_path)?;
-    Ok(())
-}
 
-fn pid_alive(pid: i32) -> Result<bool, String> {
-    if pid <= 0 {
-        return Ok(false);
-    }
-    let res = unsafe { libc::kill(pid, 0) };
-    if res == 0 {
-        return Ok(true);
-    }
-    let err = io::Error::last_os_error();
-    match err.raw_os_error() {
-        Some(code) if code == libc::ESRCH => Ok(false),
-        Some(code) if code == libc::EPERM => Ok(true),
-        _ => Err(format!("failed to check pid {pid}: {err}")),
-    }
+    let request = Request::Stop {
+        replaced: reason == EndReason::ReplacedByNewTimer,
+    };
+    let answer = control_socket_path(state.pid).and_then(|path| control::request(&path, &request));
+    let stopped = match answer {
+        Ok(Response {
+            ended: Some(ended), ..
+        }) => Stopped {
+            state: ended,
+            run_hook: false,
+        },
+        _ => {
+            // Checked again: it may have exited while we waited for an answer,
+            // and its pid gone to another process.
+            if identity::daemon_running(&state) {
+                send_sigterm(state.pid)?;
+            }
+            // A scheduled phase that never began has nothing to log.
+            if !state.scheduled {
+                end_session(&state, now_unix(), reason);
+            }
+            Stopped {
+                run_hook: !state.scheduled && reason == EndReason::Stopped,
+                state,
+            }
+        }
+    };
+    clear_state(state_path)?;
+    Ok(Some(stopped))
 }
 
 fn send_sigterm(pid: i32) -> Result<(), String> {
@@ -625,6 +1697,12 @@ fn send_sigterm(pid: i32) -> Result<(), String> {
     }
 }
 
+fn parse_sets(s: &str) -> Result<u64, String> {
+    let sets = s.parse().map_err(|_| format!("invalid number {s:?}"))?;
+    validate_sets(sets)?;
+    Ok(sets)
+}
+
 fn validate_sets(sets: u64) -> Result<(), String> {
     if sets == 0 {
         return Err("sets must be > 0".to_string());
@@ -635,48 +1713,174 @@ fn validate_sets(sets: u64) -> Result<(), String> {
     Ok(())
 }
 
-fn validate_minutes(minutes: u64) -> Result<(), String> {
-    if minutes == 0 {
-        return Err("minutes must be > 0".to_string());
+/// Checks a phase length in seconds.
+fn validate_length(secs: u64) -> Result<(), String> {
+    if secs == 0 {
+        return Err("duration must be > 0".to_string());
     }
-    if minutes > MAX_MINUTES {
-        return Err(format!("minutes too large (max {MAX_MINUTES})"));
+    if secs > MAX_MINUTES * 60 {
+        return Err(format!("duration too large (max {MAX_MINUTES} minutes)"));
     }
     Ok(())
 }
 
-fn parse_cycle(
-    sets: Option<u64>,
-    set: Option<u64>,
-    work_minutes: Option<u64>,
-    break_minutes: Option<u64>,
-) -> Result<Option<Cycle>, String> {
-    if sets.is_none() && set.is_none() && work_minutes.is_none() && break_minutes.is_none() {
-        return Ok(None);
-    }
+fn parse_extension(s: &str) -> Result<u64, String> {
+    parse_duration(s.strip_prefix('+').unwrap_or(s))
+}
 
-    let sets = sets.ok_or_else(|| "invalid cycle args: missing --sets".to_string())?;
-    let set = set.ok_or_else(|| "invalid cycle args: missing --set".to_string())?;
-    let work_minutes =
-        work_minutes.ok_or_else(|| "invalid cycle args: missing --work-minutes".to_string())?;
-    let break_minutes =
-        break_minutes.ok_or_else(|| "invalid cycle args: missing --break-minutes".to_string())?;
+fn parse_reduction(s: &str) -> Result<u64, String> {
+    parse_duration(s.strip_prefix('-').unwrap_or(s))
+}
 
-    validate_sets(sets)?;
-    if sets <= 1 {
-        return Ok(None);
+/// Parses a phase length into seconds; see `parse_span` for the syntax.
+fn parse_duration(s: &str) -> Result<u64, String> {
+    let secs = parse_span(s)?;
+    validate_length(secs)?;
+    Ok(secs)
+}
+
+/// Parses a length of time into seconds: units such as `90s`, `25m`, `1h` or
+/// `1h15m30s` (largest first), or a clock-style `25:00` or `1:30:00`. A bare
+/// number is minutes.
+fn parse_span(s: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid duration {s:?} (expected e.g. 25m, 90s, 1h15m or 1:30:00)");
+    let number = |digits: &str| -> Result<u64, String> {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        // Too many digits for a u64 is too long for a timer anyway.
+        Ok(digits.parse().unwrap_or(u64::MAX))
+    };
+
+    let secs = if s.is_empty() {
+        return Err(invalid());
+    } else if s.contains(':') {
+        let parts = s
+            .split(':')
+            .map(&number)
+            .collect::<Result<Vec<u64>, String>>()?;
+        let (hours, minutes, seconds) = match parts[..] {
+            [minutes, seconds] => (0, minutes, seconds),
+            [hours, minutes, seconds] if minutes < 60 => (hours, minutes, seconds),
+            _ => return Err(invalid()),
+        };
+        if seconds >= 60 {
+            return Err(invalid());
+        }
+        hours
+            .checked_mul(3600)
+            .and_then(|secs| secs.checked_add(minutes.checked_mul(60)?))
+            .and_then(|secs| secs.checked_add(seconds))
+    } else if s.bytes().all(|b| b.is_ascii_digit()) {
+        number(s)?.checked_mul(60)
+    } else {
+        let mut total = Some(0u64);
+        let mut rest = s;
+        let mut units = [('h', 3600), ('m', 60), ('s', 1)].into_iter();
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let amount = number(&rest[..end])?;
+            let unit = rest[end..].chars().next().ok_or_else(invalid)?;
+            // Each unit at most once, hours before minutes before seconds.
+            let (_, unit_secs) = units.find(|&(name, _)| name == unit).ok_or_else(invalid)?;
+            total = total.and_then(|total| total.checked_add(amount.checked_mul(unit_secs)?));
+            rest = &rest[end + unit.len_utf8()..];
+        }
+        total
+    };
+
+    secs.ok_or_else(|| format!("duration {s:?} too large"))
+}
+
+/// Parses `--at`: a local time today such as `14:00` or `14:00:30`, or a local
+/// date and time such as `2026-10-18 09:30`. A time that has passed is an error
+/// rather than tomorrow, so a typo can't quietly queue a timer for the next day.
+fn parse_start_time(s: &str) -> Result<i64, String> {
+    parse_start_time_at(s, now_unix())
+}
+
+/// `parse_start_time` with the clock at `now`.
+fn parse_start_time_at(s: &str, now: i64) -> Result<i64, String> {
+    let invalid =
+        || format!("invalid time {s:?} (expected e.g. 14:00, 14:00:30 or \"2026-10-18 09:30\")");
+    let numbers = |text: &str, separator: char| -> Result<Vec<libc::c_int>, String> {
+        text.split(separator)
+            .map(|part| {
+                if part.is_empty() || part.len() > 4 || !part.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                part.parse().map_err(|_| invalid())
+            })
+            .collect()
+    };
+
+    let s = s.trim();
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (Some(date), time.trim_start()),
+        None => (None, s),
+    };
+    let mut tm = local_tm(now)?;
+    if let Some(date) = date {
+        let [year, month, day] = numbers(date, '-')?[..] else {
+            return Err(invalid());
+        };
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+    }
+    let (hour, minute, second) = match numbers(time, ':')?[..] {
+        [hour, minute] => (hour, minute, 0),
+        [hour, minute, second] => (hour, minute, second),
+        _ => return Err(invalid()),
+    };
+    tm.tm_hour = hour;
+    tm.tm_min = minute;
+    tm.tm_sec = second;
+    let ts = local_mktime(tm)?;
+
+    // `mktime` normalizes out-of-range fields (the 31st of June, 25:00) and
+    // times skipped by a DST change; reading the result back catches those.
+    let back = local_tm(ts)?;
+    let fields = |tm: &libc::tm| {
+        (
+            tm.tm_year, tm.tm_mon, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec,
+        )
+    };
+    if fields(&back) != fields(&tm) {
+        return Err(invalid());
     }
-    if set == 0 || set > sets {
-        return Err("invalid cycle args: --set must be in 1..=sets".to_string());
+    if ts <= now {
+        let day = if date.is_some() { "" } else { " today" };
+        return Err(format!(
+            "{s}{day} is in the past (it is {0} now)",
+            strftime_local(now, "%H:%M")?
+        ));
     }
-    validate_minutes(work_minutes)?;
-    validate_minutes(break_minutes)?;
+    Ok(ts)
+}
 
-    Ok(Some(Cycle {
-        set,
-        sets,
-        work_minutes,
-        break_minutes,
+/// Builds the long-break settings if any of them was given, filling in defaults
+/// for the rest.
+fn parse_long_break(
+    secs: Option<u64>,
+    every: Option<u64>,
+    at_end: bool,
+) -> Result<Option<LongBreak>, String> {
+    if secs.is_none() && every.is_none() && !at_end {
+        return Ok(None);
+    }
+    let secs = secs.unwrap_or(DEFAULT_LONG_BREAK_SECS);
+    let every = every.unwrap_or(DEFAULT_LONG_BREAK_EVERY);
+    validate_length(secs)?;
+    if every == 0 {
+        return Err("long break interval must be > 0".to_string());
+    }
+    Ok(Some(LongBreak {
+        secs,
+        every,
+        at_end,
     }))
 }
 
@@ -687,6 +1891,25 @@ fn now_unix() -> i64 {
         .as_secs() as i64
 }
 
+/// A length the way `parse_duration` reads it: `25m`, `1h15m`, `1m30s`.
+fn format_length(total_secs: u64) -> String {
+    let parts = [
+        (total_secs / 3600, 'h'),
+        ((total_secs % 3600) / 60, 'm'),
+        (total_secs % 60, 's'),
+    ];
+    let length: String = parts
+        .iter()
+        .filter(|&&(amount, _)| amount > 0)
+        .map(|(amount, unit)| format!("{amount}{unit}"))
+        .collect();
+    if length.is_empty() {
+        "0s".to_string()
+    } else {
+        length
+    }
+}
+
 fn format_duration(total_secs: u64) -> String {
     let hours = total_secs / 3600;
     let minutes = (total_secs % 3600) / 60;
@@ -699,14 +1922,39 @@ fn format_duration(total_secs: u64) -> String {
 }
 
 fn format_local_time(ts: i64) -> Result<String, String> {
-    let t = ts as libc::time_t;
-    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
-    let tm_ptr = unsafe { libc::localtime_r(&t, &mut tm) };
-    if tm_ptr.is_null() {
-        return Err("failed to convert timestamp to local time".to_string());
+    strftime_local(ts, "%Y-%m-%d %H:%M:%S")
+}
+
+/// When a scheduled phase begins: just the time if that is today, with the
+/// date otherwise.
+fn format_start_time(ts: i64) -> Result<String, String> {
+    if local_day_start(ts)? == local_day_start(now_unix())? {
+        strftime_local(ts, "%H:%M:%S")
+    } else {
+        strftime_local(ts, "%Y-%m-%d %H:%M:%S")
     }
+}
+
+/// `ts` as an RFC 3339 timestamp in local time, e.g. `2024-05-01T09:30:00+02:00`.
+fn format_rfc3339(ts: i64) -> Result<String, String> {
+    let offset_minutes = local_tm(ts)?.tm_gmtoff / 60;
+    let sign = if offset_minutes < 0 { '-' } else { '+' };
+    let offset_minutes = offset_minutes.abs();
+    Ok(format!(
+        "{0}{sign}{1:02}:{2:02}",
+        strftime_local(ts, "%Y-%m-%dT%H:%M:%S")?,
+        offset_minutes / 60,
+        offset_minutes % 60
+    ))
+}
 
-    let fmt = CString::new("%Y-%m-%d %H:%M:%S").map_err(|e| e.to_string())?;
+fn format_local_date(ts: i64) -> Result<String, String> {
+    strftime_local(ts, "%Y-%m-%d %a")
+}
+
+fn strftime_local(ts: i64, fmt: &str) -> Result<String, String> {
+    let tm = local_tm(ts)?;
+    let fmt = CString::new(fmt).map_err(|e| e.to_string())?;
     let mut buf = [0i8; 64];
     let len = unsafe { libc::strftime(buf.as_mut_ptr(), buf.len(), fmt.as_ptr(), &tm) };
     if len == 0 {
@@ -716,6 +1964,68 @@ fn format_local_time(ts: i64) -> Result<String, String> {
     Ok(cstr.to_string_lossy().into_owned())
 }
 
+fn local_tm(ts: i64) -> Result<libc::tm, String> {
+    let t = ts as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let tm_ptr = unsafe { libc::localtime_r(&t, &mut tm) };
+    if tm_ptr.is_null() {
+        return Err("failed to convert timestamp to local time".to_string());
+    }
+    Ok(tm)
+}
+
+/// Converts broken-down local time back to a timestamp. `tm_isdst = -1` lets
+/// `mktime` work out whether DST applies, so days around a DST change come out as
+/// 23 or 25 hours long instead of being shifted by an hour.
+fn local_mktime(mut tm: libc::tm) -> Result<i64, String> {
+    tm.tm_isdst = -1;
+    let t = unsafe { libc::mktime(&mut tm) };
+    if t == -1 {
+        return Err("failed to convert local time to timestamp".to_string());
+    }
+    Ok(t as i64)
+}
+
+/// Local midnight at the start of the day containing `ts`.
+fn local_day_start(ts: i64) -> Result<i64, String> {
+    local_day_start_at(ts, 0)
+}
+
+/// Local midnight `days` calendar days after the midnight `day_start`.
+fn add_local_days(day_start: i64, days: i64) -> Result<i64, String> {
+    add_local_days_at(day_start, days, 0)
+}
+
+/// The start of the day containing `ts`, for days that begin at `hour` o'clock
+/// local time instead of midnight.
+fn local_day_start_at(ts: i64, hour: u32) -> Result<i64, String> {
+    let mut tm = local_tm(ts)?;
+    if tm.tm_hour < hour as libc::c_int {
+        tm.tm_mday -= 1;
+    }
+    tm.tm_hour = hour as libc::c_int;
+    tm.tm_min = 0;
+    tm.tm_sec = 0;
+    local_mktime(tm)
+}
+
+/// `hour` o'clock `days` calendar days after the day starting at `day_start`.
+fn add_local_days_at(day_start: i64, days: i64, hour: u32) -> Result<i64, String> {
+    let mut tm = local_tm(day_start)?;
+    tm.tm_mday += days as libc::c_int;
+    tm.tm_hour = hour as libc::c_int;
+    tm.tm_min = 0;
+    tm.tm_sec = 0;
+    local_mktime(tm)
+}
+
+/// Local midnight at the start of the Monday-based week containing `ts`.
+fn local_week_start(ts: i64) -> Result<i64, String> {
+    let tm = local_tm(ts)?;
+    let days_since_monday = (tm.tm_wday as i64 + 6) % 7;
+    add_local_days(local_day_start(ts)?, -days_since_monday)
+}
+
 fn create_pipe() -> Result<(i32, i32), String> {
     let mut fds = [0i32; 2];
     let res = unsafe { libc::pipe(fds.as_mut_ptr()) };
@@ -725,20 +2035,426 @@ fn create_pipe() -> Result<(i32, i32), String> {
     Ok((fds[0], fds[1]))
 }
 
-fn wait_for_ready_fd(fd: i32) {
+/// Waits for the daemon to report that it is ready. `false` means it exited first.
+fn wait_for_daemon(fd: i32) -> bool {
     let mut buf = [0u8; 1];
-    loop {
+    let ready = loop {
         let n = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), 1) };
-        if n == 1 || n == 0 {
-            break;
+        if n >= 0 {
+            break n == 1;
         }
-        let err = io::Error::last_os_error();
-        if err.raw_os_error() == Some(libc::EINTR) {
-            continue;
+        if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            break false;
         }
-        break;
-    }
+    };
     unsafe {
         libc::close(fd);
     }
+    ready
+}
+
+/// The terminal `tock` is running in, if any. The daemon detaches from it, so
+/// this is looked up before spawning.
+fn controlling_tty() -> Option<PathBuf> {
+    [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO]
+        .into_iter()
+        .find_map(|fd| {
+            let mut buf = [0 as libc::c_char; 256];
+            let res = unsafe { libc::ttyname_r(fd, buf.as_mut_ptr(), buf.len()) };
+            if res != 0 {
+                return None;
+            }
+            let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+            Some(PathBuf::from(name.to_str().ok()?))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Central European time as a POSIX rule, so it needs no tz database:
+    /// clocks go forward at 02:00 on the last Sunday of March and back at
+    /// 03:00 on the last Sunday of October.
+    pub(crate) const CET: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+    /// 2026-01-10 10:00 in `CET`.
+    pub(crate) const JAN_10_10AM: i64 = 1768035600;
+
+    extern "C" {
+        fn tzset();
+    }
+
+    /// Runs `f` with local time in `tz`. Tests that use local time share the
+    /// process's `TZ`, so they take turns.
+    pub(crate) fn in_tz<T>(tz: &str, f: impl FnOnce() -> T) -> T {
+        static TZ_LOCK: Mutex<()> = Mutex::new(());
+        let _guard = TZ_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("TZ", tz);
+        unsafe { tzset() };
+        f()
+    }
+
+    /// A `mode` phase of `secs` that began at `start_ts`, run by pid 42.
+    pub(crate) fn running(mode: Mode, start_ts: i64, secs: u64) -> State {
+        State {
+            pid: 42,
+            mode,
+            start_ts,
+            end_ts: start_ts + secs as i64,
+            secs,
+            sequence: None,
+            meta: Meta::default(),
+            paused: None,
+            paused_secs: 0,
+            scheduled: false,
+            awaiting_ack: false,
+            box_pid: None,
+            identity: None,
+        }
+    }
+
+    /// Local midnight starting 2026-03-29 in `CET`, the day clocks go forward.
+    const MAR_29: i64 = 1774738800;
+    /// Local midnight starting 2026-10-25 in `CET`, the day clocks go back.
+    const OCT_25: i64 = 1792879200;
+
+    pub(crate) fn session(mode: Mode, start_ts: i64, end_ts: i64, paused_secs: u64) -> LogEntry {
+        LogEntry {
+            id: String::new(),
+            mode,
+            planned_secs: (end_ts - start_ts) as u64,
+            start_ts,
+            end_ts,
+            set: None,
+            sets: None,
+            end_reason: EndReason::Completed,
+            meta: Meta::default(),
+            paused_secs,
+            while_suspended: false,
+            exit_status: None,
+            overtime_secs: 0,
+        }
+    }
+
+    #[test]
+    fn local_days_follow_dst_changes() {
+        in_tz(CET, || {
+            assert_eq!(local_day_start(MAR_29), Ok(MAR_29));
+            assert_eq!(local_day_start(MAR_29 + 12 * 3600), Ok(MAR_29));
+            assert_eq!(local_day_start(MAR_29 - 1), Ok(MAR_29 - 24 * 3600));
+            assert_eq!(add_local_days(MAR_29, 1), Ok(MAR_29 + 23 * 3600));
+            assert_eq!(add_local_days(MAR_29, -1), Ok(MAR_29 - 24 * 3600));
+            assert_eq!(local_day_start(MAR_29 + 23 * 3600 - 1), Ok(MAR_29));
+
+            assert_eq!(add_local_days(OCT_25, 1), Ok(OCT_25 + 25 * 3600));
+            assert_eq!(local_day_start(OCT_25 + 24 * 3600 + 1800), Ok(OCT_25));
+            // Counting back across the summer lands on midnight again.
+            assert_eq!(add_local_days(OCT_25, -210), Ok(MAR_29));
+        });
+    }
+
+    #[test]
+    fn local_days_can_start_at_another_hour() {
+        in_tz(CET, || {
+            let four_am = MAR_29 + 3 * 3600;
+            assert_eq!(local_day_start_at(MAR_29 + 3600, 4), Ok(MAR_29 - 20 * 3600));
+            assert_eq!(local_day_start_at(four_am, 4), Ok(four_am));
+            assert_eq!(add_local_days_at(four_am, 1, 4), Ok(four_am + 24 * 3600));
+            assert_eq!(add_local_days_at(four_am, -1, 4), Ok(four_am - 23 * 3600));
+        });
+    }
+
+    #[test]
+    fn summaries_split_sessions_at_local_midnight() {
+        in_tz(CET, || {
+            let day = |start: i64| (start, add_local_days(start, 1).unwrap());
+            let (sat, sun) = (day(MAR_29 - 24 * 3600), day(MAR_29));
+            let entries = [
+                // 23:40 to 00:10.
+                session(Mode::Work, MAR_29 - 1200, MAR_29 + 600, 0),
+                // 01:50 to 03:10, twenty minutes as the clocks go forward.
+                session(Mode::Work, MAR_29 + 6600, MAR_29 + 7800, 0),
+                // An hour around the next midnight, a third of it paused.
+                session(Mode::Break, sun.1 - 1800, sun.1 + 1800, 1200),
+            ];
+
+            let saturday = Summary::for_range(&entries, sat.0, sat.1);
+            assert_eq!(saturday.work_secs, 1200);
+            assert_eq!(saturday.pomodoros, 0);
+
+            let sunday = Summary::for_range(&entries, sun.0, sun.1);
+            assert_eq!(sunday.work_secs, 600 + 1200);
+            assert_eq!(sunday.pomodoros, 2);
+            assert_eq!(sunday.break_secs, 1200);
+            assert_eq!(sunday.paused_secs, 600);
+
+            let monday = Summary::for_range(&entries, sun.1, add_local_days(sun.1, 1).unwrap());
+            assert_eq!((monday.work_secs, monday.break_secs), (0, 1200));
+            assert_eq!(monday.paused_secs, 600);
+        });
+    }
+
+    #[test]
+    fn start_times_are_local() {
+        in_tz(CET, || {
+            let at = |s: &str| parse_start_time_at(s, JAN_10_10AM);
+            assert_eq!(at("14:00"), Ok(1768050000));
+            assert_eq!(at(" 14:00:30 "), Ok(1768050030));
+            assert_eq!(at("2026-01-11 09:30"), Ok(1768120200));
+            assert_eq!(at("2026-1-11T09:30"), Ok(1768120200));
+            assert_eq!(at("2026-01-11  9:30:00"), Ok(1768120200));
+        });
+    }
+
+    #[test]
+    fn start_times_in_the_past_are_refused() {
+        in_tz(CET, || {
+            let at = |s: &str| parse_start_time_at(s, JAN_10_10AM);
+            assert_eq!(
+                at("09:00"),
+                Err("09:00 today is in the past (it is 10:00 now)".to_string())
+            );
+            assert_eq!(
+                at("10:00"),
+                Err("10:00 today is in the past (it is 10:00 now)".to_string())
+            );
+            assert_eq!(
+                at("2026-01-09 12:00"),
+                Err("2026-01-09 12:00 is in the past (it is 10:00 now)".to_string())
+            );
+            assert!(at("10:00:01").is_ok());
+        });
+    }
+
+    #[test]
+    fn start_times_mktime_would_normalize_are_refused() {
+        in_tz(CET, || {
+            let at = |s: &str| parse_start_time_at(s, JAN_10_10AM);
+            for bad in [
+                "25:00",
+                "14:60",
+                "14:00:60",
+                "2026-06-31 09:00",
+                "2026-02-29 09:00",
+                "2026-13-01 09:00",
+                "2026-06-00 09:00",
+            ] {
+                assert_eq!(
+                    at(bad),
+                    Err(format!(
+                        "invalid time {bad:?} (expected e.g. 14:00, 14:00:30 or \"2026-10-18 09:30\")"
+                    ))
+                );
+            }
+            assert!(at("2028-02-29 09:00").is_ok());
+        });
+    }
+
+    #[test]
+    fn start_times_in_a_dst_gap_are_refused() {
+        in_tz(CET, || {
+            let at = |s: &str| parse_start_time_at(s, JAN_10_10AM);
+            assert!(at("2026-03-29 02:30").is_err());
+            assert_eq!(at("2026-03-29 03:30"), Ok(1774747800));
+            // Half past two happens twice when the clocks go back; either will do.
+            let repeated = at("2026-10-25 02:30").unwrap();
+            assert!([1792888200, 1792891800].contains(&repeated));
+        });
+    }
+
+    #[test]
+    fn start_times_must_be_well_formed() {
+        for bad in [
+            "",
+            "14",
+            "14:00:00:00",
+            "2026-01-11",
+            "2026-01 09:00",
+            "12345:00",
+            "14:0a",
+            "-1:00",
+        ] {
+            assert!(
+                in_tz(CET, || parse_start_time_at(bad, JAN_10_10AM)).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    /// State as an older version wrote it, with `fields` added.
+    fn old_state(mode: &str, fields: &str) -> Result<State, serde_json::Error> {
+        serde_json::from_str(&format!(
+            r#"{{"pid": 1, "mode": "{mode}", "start_ts": 0, "end_ts": 1500, "minutes": 25{fields}}}"#
+        ))
+    }
+
+    #[test]
+    fn parse_span_reads_clock_style() {
+        assert_eq!(parse_span("25:00"), Ok(1500));
+        assert_eq!(parse_span("90:00"), Ok(5400));
+        assert_eq!(parse_span("1:30:00"), Ok(5400));
+        assert_eq!(parse_span("0:00:45"), Ok(45));
+        for bad in [
+            "0:90", "1:60:00", "1:30:60", "1:2:3:4", ":30", "25:", "1::00", "-1:00",
+        ] {
+            assert!(parse_span(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parse_span_reads_units_largest_first() {
+        assert_eq!(parse_span("25"), Ok(1500));
+        assert_eq!(parse_span("90s"), Ok(90));
+        assert_eq!(parse_span("25m"), Ok(1500));
+        assert_eq!(parse_span("1h15m"), Ok(4500));
+        assert_eq!(parse_span("1h15m30s"), Ok(4530));
+        assert_eq!(parse_span("1h30s"), Ok(3630));
+        assert_eq!(parse_span("90m"), Ok(5400));
+        for bad in [
+            "", "m", "15m1h", "30s5m", "1m1m", "25x", "25 m", "1h 15m", "1.5h", "25M",
+        ] {
+            assert_eq!(
+                parse_span(bad),
+                Err(format!(
+                    "invalid duration {bad:?} (expected e.g. 25m, 90s, 1h15m or 1:30:00)"
+                )),
+            );
+        }
+    }
+
+    #[test]
+    fn parse_span_reports_overflow() {
+        let huge = "99999999999999999999";
+        assert_eq!(
+            parse_span(huge),
+            Err(format!("duration {huge:?} too large"))
+        );
+        assert!(parse_span(&format!("{huge}h"))
+            .unwrap_err()
+            .ends_with("too large"));
+        assert!(parse_span("5124095576030432h")
+            .unwrap_err()
+            .ends_with("too large"));
+        assert!(parse_span(&format!("{huge}:00:00"))
+            .unwrap_err()
+            .ends_with("too large"));
+        assert!(parse_span(&format!("{0}m{0}s", u64::MAX / 60))
+            .unwrap_err()
+            .ends_with("too large"));
+    }
+
+    #[test]
+    fn parse_duration_checks_the_range() {
+        assert_eq!(parse_duration("1s"), Ok(1));
+        assert_eq!(
+            parse_duration(&MAX_MINUTES.to_string()),
+            Ok(MAX_MINUTES * 60)
+        );
+        assert_eq!(parse_duration("0"), Err("duration must be > 0".to_string()));
+        assert_eq!(
+            parse_duration("0:00"),
+            Err("duration must be > 0".to_string())
+        );
+        assert_eq!(
+            parse_duration(&format!("{MAX_MINUTES}m1s")),
+            Err(format!("duration too large (max {MAX_MINUTES} minutes)"))
+        );
+    }
+
+    #[test]
+    fn extensions_and_reductions_take_an_optional_sign() {
+        assert_eq!(parse_extension("+5m"), Ok(300));
+        assert_eq!(parse_extension("90s"), Ok(90));
+        assert_eq!(parse_reduction("-5m"), Ok(300));
+        assert_eq!(parse_reduction("1:00"), Ok(60));
+        for bad in ["++5", "-5", "+", "+0"] {
+            assert!(parse_extension(bad).is_err(), "{bad}");
+        }
+        for bad in ["--5", "+5", "-", "-0"] {
+            assert!(parse_reduction(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn extensions_and_reductions_are_bounded() {
+        let max = format!("+{MAX_MINUTES}");
+        assert_eq!(parse_extension(&max), Ok(MAX_MINUTES * 60));
+        let too_large = Err(format!("duration too large (max {MAX_MINUTES} minutes)"));
+        assert_eq!(parse_extension(&format!("{max}m1s")), too_large);
+        assert_eq!(parse_reduction(&format!("-{MAX_MINUTES}m1s")), too_large);
+        let huge = format!("+{}s", u64::MAX);
+        assert!(parse_extension(&huge).unwrap_err().contains("too large"));
+        assert!(parse_reduction("-99999999999999999999h")
+            .unwrap_err()
+            .ends_with("too large"));
+    }
+
+    #[test]
+    fn minutes_in_old_state_become_secs() {
+        let state = old_state("work", "").unwrap();
+        assert_eq!(state.secs, 1500);
+        let state: State = serde_json::from_str(
+            r#"{"pid": 1, "mode": "work", "start_ts": 0, "end_ts": 90, "secs": 90, "minutes": 2}"#,
+        )
+        .unwrap();
+        assert_eq!(state.secs, 90);
+        let err = serde_json::from_str::<State>(
+            r#"{"pid": 1, "mode": "work", "start_ts": 0, "end_ts": 90}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "missing field `secs`");
+    }
+
+    #[test]
+    fn long_breaks_in_minutes_become_secs() {
+        let long_break: LongBreak = serde_json::from_str(r#"{"minutes": 15, "every": 4}"#).unwrap();
+        assert_eq!(
+            long_break,
+            LongBreak {
+                secs: 900,
+                every: 4,
+                at_end: false
+            }
+        );
+        let long_break: LongBreak =
+            serde_json::from_str(r#"{"secs": 90, "every": 2, "at_end": true}"#).unwrap();
+        assert_eq!(long_break.secs, 90);
+        assert!(long_break.at_end);
+        let err = serde_json::from_str::<LongBreak>(r#"{"every": 4}"#).unwrap_err();
+        assert_eq!(err.to_string(), "missing field `secs`");
+    }
+
+    #[test]
+    fn cycle_records_become_sequences_at_the_same_phase() {
+        let cycle = r#", "cycle": {"set": 2, "sets": 3, "work_minutes": 25, "break_minutes": 5}"#;
+        let sequence = old_state("work", cycle).unwrap().sequence.unwrap();
+        assert_eq!(sequence.to_string(), "(25w 5b)x2 25w");
+        assert_eq!((sequence.step(), sequence.set()), (3, 2));
+
+        let sequence = old_state("break", cycle).unwrap().sequence.unwrap();
+        assert_eq!((sequence.step(), sequence.set()), (4, 2));
+        assert_eq!(sequence.current().mode, Mode::Break);
+    }
+
+    #[test]
+    fn cycle_records_keep_their_long_breaks() {
+        let cycle = r#", "cycle": {"set": 2, "sets": 2, "work_secs": 1500, "break_secs": 300,
+            "long_break": {"minutes": 15, "every": 4, "at_end": true}}"#;
+        let sequence = old_state("long_break", cycle).unwrap().sequence.unwrap();
+        assert_eq!(sequence.to_string(), "25w 5b 25w 15lb");
+        assert_eq!(sequence.step(), 4);
+        assert_eq!(sequence.current().mode, Mode::LongBreak);
+    }
+
+    #[test]
+    fn cycle_records_out_of_range_are_rejected() {
+        let cycle = r#", "cycle": {"set": 3, "sets": 3, "work_minutes": 25, "break_minutes": 5}"#;
+        assert!(old_state("work", cycle).is_ok());
+        let err = old_state("break", cycle).unwrap_err().to_string();
+        assert_eq!(err, "step must be 1 to 5");
+        let cycle = r#", "cycle": {"set": 1, "sets": 1, "work_minutes": 25}"#;
+        let err = old_state("work", cycle).unwrap_err().to_string();
+        assert_eq!(err, "missing field `break_secs`");
+    }
 }
@@ -0,0 +1,403 @@
+//! Announces finished and scheduled phases, and timeboxes and budgets running
+//! out, through one or more notification backends.
+//!
+//! The backends are picked in the `[notify]` section of the config file and
+//! every notification fans out to all of them; one failing doesn't keep the
+//! others from running. By default the platform's desktop backend is used:
+//! Notification Center through `osascript` on macOS, the freedesktop
+//! notification service on the session bus everywhere else.
+
+use crate::config::{Backend, NotifyConfig};
+use crate::dbus::{self, DesktopNotification, Urgency};
+use crate::hooks;
+use crate::Mode;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const APP_NAME: &str = "tock";
+const TITLE: &str = "Pomodoro";
+const ICON: &str = "appointment-soon";
+/// How long a normal notification stays on screen. Servers keep critical ones
+/// up until they are dismissed, whatever the timeout.
+const EXPIRE_TIMEOUT_MS: i32 = 10_000;
+
+pub(crate) struct Notification {
+    /// The phase the notification is about.
+    pub(crate) mode: Mode,
+    pub(crate) title: String,
+    pub(crate) body: String,
+    pub(crate) urgency: Urgency,
+    /// How many times audible backends beep.
+    pub(crate) beeps: u32,
+}
+
+impl Notification {
+    /// The message for the end of `finished`. `next` is the phase starting now, if
+    /// any. `while_suspended` says the phase ran out while the machine was asleep,
+    /// so the notification comes late.
+    pub(crate) fn phase_finished(
+        finished: Mode,
+        next: Option<Mode>,
+        while_suspended: bool,
+    ) -> Notification {
+        let (phase, then, beeps) = match (finished, next) {
+            (Mode::Work, Some(Mode::LongBreak)) => ("Work", "Time for a long break.", 2),
+            (Mode::Work, _) => ("Work", "Time for a break.", 2),
+            (Mode::Break, _) => ("Break", "Back to work.", 1),
+            (Mode::LongBreak, Some(_)) => ("Long break", "Back to work.", 1),
+            (Mode::LongBreak, None) => ("Long break", "Cycle complete.", 1),
+        };
+        let when = if while_suspended {
+            " while the computer was asleep"
+        } else {
+            ""
+        };
+        // Getting back to work is the one people tend to miss, so the end of a
+        // break is critical and stays on screen until it is dismissed; the end
+        // of work goes away after `EXPIRE_TIMEOUT_MS`.
+        let urgency = if finished == Mode::Work {
+            Urgency::Normal
+        } else {
+            Urgency::Critical
+        };
+        Notification {
+            mode: finished,
+            title: TITLE.to_string(),
+            body: format!("{phase} finished{when}. {then}"),
+            urgency,
+            beeps,
+        }
+    }
+
+    /// The end of `finished` in a sequence that waits for `tock next` before
+    /// `next`. Critical whatever the phase, since nothing moves on without it.
+    pub(crate) fn awaiting_ack(finished: Mode, next: Mode, while_suspended: bool) -> Notification {
+        let then = match next {
+            Mode::Work => "Run `tock next` to get back to work.",
+            Mode::Break => "Run `tock next` to start the break.",
+            Mode::LongBreak => "Run `tock next` to start the long break.",
+        };
+        let when = if while_suspended {
+            " while the computer was asleep"
+        } else {
+            ""
+        };
+        Notification {
+            mode: finished,
+            title: TITLE.to_string(),
+            body: format!("{0} finished{when}. {then}", phase_name(finished)),
+            urgency: Urgency::Critical,
+            beeps: 2,
+        }
+    }
+
+    /// A project's time budget reaching `percent` of its limit; `body` says which
+    /// and how far.
+    pub(crate) fn budget(percent: u64, body: String) -> Notification {
+        let urgency = if percent >= 100 {
+            Urgency::Critical
+        } else {
+            Urgency::Normal
+        };
+        Notification {
+            mode: Mode::Work,
+            title: TITLE.to_string(),
+            body,
+            urgency,
+            beeps: 1,
+        }
+    }
+
+    /// A `tock box` running `command` has `left` to go.
+    pub(crate) fn timebox_warning(command: &str, left: &str) -> Notification {
+        Notification {
+            mode: Mode::Work,
+            title: TITLE.to_string(),
+            body: format!("{left} left for {command}."),
+            urgency: Urgency::Normal,
+            beeps: 1,
+        }
+    }
+
+    /// A `tock box` ran out; `then` says what happens to the command.
+    pub(crate) fn timebox_over(command: &str, then: &str) -> Notification {
+        Notification {
+            mode: Mode::Work,
+            title: TITLE.to_string(),
+            body: format!("Time's up for {command}. {then}"),
+            urgency: Urgency::Critical,
+            beeps: 2,
+        }
+    }
+
+    /// The message for a scheduled phase beginning.
+    pub(crate) fn phase_started(mode: Mode) -> Notification {
+        Notification {
+            mode,
+            title: TITLE.to_string(),
+            body: format!("{0} starts now.", phase_name(mode)),
+            urgency: Urgency::Normal,
+            beeps: 1,
+        }
+    }
+}
+
+fn phase_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Work => "Work",
+        Mode::Break => "Break",
+        Mode::LongBreak => "Long break",
+    }
+}
+
+pub(crate) trait Notifier {
+    /// Backend name used in error messages.
+    fn name(&self) -> &'static str;
+    fn notify(&self, notification: &Notification) -> Result<(), String>;
+}
+
+/// Builds the notifier described by `config`. `tty` is the terminal the timer
+/// was started from, which the bell backend rings.
+pub(crate) fn from_config(config: &NotifyConfig, tty: Option<&Path>) -> FanOut {
+    let notifiers = config
+        .backends
+        .iter()
+        .map(|backend| -> Box<dyn Notifier> {
+            match backend {
+                Backend::Desktop if cfg!(target_os = "macos") => Box::new(Osascript),
+                Backend::Desktop => Box::new(Freedesktop),
+                Backend::Osascript => Box::new(Osascript),
+                Backend::Dbus => Box::new(Freedesktop),
+                Backend::Command => Box::new(CommandNotifier {
+                    template: config.command.clone(),
+                }),
+                Backend::Bell => Box::new(Bell {
+                    tty: tty.map(Path::to_path_buf),
+                }),
+                Backend::None => Box::new(Noop),
+            }
+        })
+        .collect();
+    FanOut(notifiers)
+}
+
+/// Sends every notification to all of its backends.
+pub(crate) struct FanOut(Vec<Box<dyn Notifier>>);
+
+impl Notifier for FanOut {
+    fn name(&self) -> &'static str {
+        "fan-out"
+    }
+
+    /// Tries every backend, then reports all the ones that failed.
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let errors: Vec<String> = self
+            .0
+            .iter()
+            .filter_map(|notifier| {
+                notifier
+                    .notify(notification)
+                    .err()
+                    .map(|e| format!("{0}: {e}", notifier.name()))
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// macOS Notification Center, plus audible beeps.
+struct Osascript;
+
+impl Notifier for Osascript {
+    fn name(&self) -> &'static str {
+        "osascript"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let script = format!(
+            "display notification {0} with title {1}",
+            applescript_string(&notification.body),
+            applescript_string(&notification.title)
+        );
+        run_osascript(&script)?;
+        for _ in 0..notification.beeps {
+            run_osascript("beep")?;
+        }
+        Ok(())
+    }
+}
+
+fn run_osascript(script: &str) -> Result<(), String> {
+    let status = Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .status()
+        .map_err(|e| format!("failed to run osascript: {e}"))?;
+    if !status.success() {
+        return Err(format!("osascript failed ({status})"));
+    }
+    Ok(())
+}
+
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `org.freedesktop.Notifications` on the session bus.
+struct Freedesktop;
+
+impl Notifier for Freedesktop {
+    fn name(&self) -> &'static str {
+        "dbus"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let mut conn = dbus::Connection::session()?;
+        let sound = match notification.urgency {
+            Urgency::Critical => "alarm-clock-elapsed",
+            _ => "complete",
+        };
+        dbus::notify(
+            &mut conn,
+            &DesktopNotification {
+                app_name: APP_NAME,
+                icon: ICON,
+                summary: &notification.title,
+                body: &notification.body,
+                urgency: notification.urgency,
+                sound: Some(sound),
+                expire_timeout_ms: EXPIRE_TIMEOUT_MS,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+/// Runs a user-supplied shell command. `{mode}`, `{title}` and `{body}` in the
+/// template are replaced with shell-quoted values, so they are always safe to
+/// use as separate words. Like a hook, it is killed if it runs too long.
+struct CommandNotifier {
+    template: Option<String>,
+}
+
+impl Notifier for CommandNotifier {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let template = self
+            .template
+            .as_deref()
+            .ok_or_else(|| "notify.command is not set".to_string())?;
+        let command = fill_template(template, notification);
+        let mut child = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(&command)
+            // Its own process group, so a timeout also kills anything it started.
+            .process_group(0)
+            .spawn()
+            .map_err(|e| format!("failed to run {command:?}: {e}"))?;
+        hooks::wait_or_kill(&mut child, &format!("{command:?}"))
+    }
+}
+
+/// Replaces the placeholders in one pass, so a value that itself contains
+/// `{title}` or `{body}` is left as it is.
+fn fill_template(template: &str, notification: &Notification) -> String {
+    let mut command = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        command.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = [
+            ("{mode}", notification.mode.as_str()),
+            ("{title}", notification.title.as_str()),
+            ("{body}", notification.body.as_str()),
+        ]
+        .into_iter()
+        .find(|(placeholder, _)| rest.starts_with(placeholder));
+        match value {
+            Some((placeholder, value)) => {
+                command.push_str(&shell_quote(value));
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                command.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    command.push_str(rest);
+    command
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Rings the terminal bell on the terminal the timer was started from.
+struct Bell {
+    tty: Option<PathBuf>,
+}
+
+impl Notifier for Bell {
+    fn name(&self) -> &'static str {
+        "bell"
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<(), String> {
+        let tty = self
+            .tty
+            .as_deref()
+            .ok_or_else(|| "the timer was not started from a terminal".to_string())?;
+        let mut terminal = OpenOptions::new()
+            .write(true)
+            .open(tty)
+            .map_err(|e| format!("failed to open {tty:?}: {e}"))?;
+        let bells = "\x07".repeat(notification.beeps as usize);
+        terminal
+            .write_all(bells.as_bytes())
+            .map_err(|e| format!("failed to write to {tty:?}: {e}"))
+    }
+}
+
+/// Does nothing; for when the log is all you want.
+struct Noop;
+
+impl Notifier for Noop {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn notify(&self, _notification: &Notification) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_placeholders_are_filled_once() {
+        let notification = Notification {
+            mode: Mode::LongBreak,
+            title: "{body} it's".to_string(),
+            body: "{mode}".to_string(),
+            urgency: Urgency::Normal,
+            beeps: 1,
+        };
+        assert_eq!(
+            fill_template("notify {title} {body} {mode} {other} {", &notification),
+            r"notify '{body} it'\''s' '{mode}' 'long_break' {other} {"
+        );
+    }
+}
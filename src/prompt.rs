@@ -0,0 +1,252 @@
+//! `tock prompt`: a timer segment for shell prompts.
+//!
+//! This runs on every prompt, so the common invocations are recognised before
+//! clap is set up, and a live-looking state file is trusted without probing the
+//! daemon. Nothing is printed when no timer is running, or when anything goes
+//! wrong; a prompt is no place for error messages.
+
+use crate::{format_duration, identity, now_unix, state_paths, Mode, State};
+use clap::ValueEnum;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+pub(crate) const DEFAULT_FORMAT: &str = "{icon} {remaining}";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const BASH_INIT: &str = r#"__tock_prompt() {
+    local segment
+    segment=$(command tock prompt 2>/dev/null)
+    [ -n "$segment" ] && printf '%s ' "$segment"
+}
+case "$PS1" in
+    *__tock_prompt*) ;;
+    *) PS1='$(__tock_prompt)'"$PS1" ;;
+esac
+"#;
+
+const ZSH_INIT: &str = r#"setopt prompt_subst
+__tock_prompt() {
+    local segment
+    segment=$(command tock prompt 2>/dev/null)
+    [[ -n $segment ]] && print -rn -- "${segment//\%/%%} "
+}
+[[ $PROMPT == *__tock_prompt* ]] || PROMPT='$(__tock_prompt)'"$PROMPT"
+"#;
+
+const FISH_INIT: &str = r#"function __tock_prompt --description 'tock timer segment'
+    set -l segment (command tock prompt 2>/dev/null)
+    test -n "$segment"; and printf '%s ' $segment
+end
+if not functions -q __tock_original_fish_prompt
+    functions -c fish_prompt __tock_original_fish_prompt
+    function fish_prompt
+        __tock_prompt
+        __tock_original_fish_prompt
+    end
+end
+"#;
+
+/// The shell code that `tock prompt init <shell>` prints.
+pub(crate) fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH_INIT,
+        Shell::Zsh => ZSH_INIT,
+        Shell::Fish => FISH_INIT,
+    }
+}
+
+/// Returns the format when the arguments are a plain `prompt` invocation:
+/// `tock prompt`, `tock prompt --format F` or `tock prompt --format=F`.
+/// Anything else (`init`, `--help`, typos) goes through clap as usual.
+pub(crate) fn fast_path_format(mut args: impl Iterator<Item = OsString>) -> Option<String> {
+    args.next()?;
+    if args.next()? != "prompt" {
+        return None;
+    }
+    let format = match args.next() {
+        None => return Some(DEFAULT_FORMAT.to_string()),
+        Some(arg) if arg == "--format" => args.next()?.into_string().ok()?,
+        Some(arg) => arg
+            .into_string()
+            .ok()?
+            .strip_prefix("--format=")?
+            .to_string(),
+    };
+    args.next().is_none().then_some(format)
+}
+
+/// Prints the segment for the running timer, if there is one.
+pub(crate) fn print(format: &str) {
+    let Some(state) = live_state() else {
+        return;
+    };
+    let segment = render(format, &state, now_unix());
+    let mut stdout = std::io::stdout().lock();
+    let _ = stdout.write_all(segment.as_bytes());
+    let _ = stdout.write_all(b"\n");
+}
+
+/// Fills in `{icon}`, `{mode}`, `{remaining}`, `{set}` and `{task}`. A scheduled
+/// timer's `{remaining}` counts down to its start, and one waiting for `tock
+/// next` counts up from its end, e.g. `+03:12`.
+fn render(format: &str, state: &State, now: i64) -> String {
+    let set = state
+        .sequence
+        .as_ref()
+        .map(|sequence| format!("{0}/{1}", sequence.set(), sequence.sets()))
+        .unwrap_or_default();
+    let remaining = match state.overdue_secs(now) {
+        Some(overdue_secs) => format!("+{0}", format_duration(overdue_secs)),
+        None => format_duration(state.countdown_secs(now)),
+    };
+    format
+        .replace("{icon}", icon(state))
+        .replace("{mode}", &state.mode.to_string())
+        .replace("{remaining}", &remaining)
+        .replace("{set}", &set)
+        .replace("{task}", state.meta.task.as_deref().unwrap_or(""))
+}
+
+/// The phase's icon; `tock bar` uses the same ones.
+pub(crate) fn icon(state: &State) -> &'static str {
+    if state.scheduled {
+        return "⏳";
+    }
+    match (state.paused, state.mode) {
+        (Some(_), _) => "⏸",
+        (None, Mode::Work) => "🍅",
+        (None, Mode::Break) => "☕",
+        (None, Mode::LongBreak) => "🌴",
+    }
+}
+
+/// The running timer's state, without writing anything. State that is still
+/// counting down and was written since boot is trusted as is; only a paused or
+/// overdue timer, whose daemon may have gone away unnoticed, costs a look at the process.
+pub(crate) fn live_state() -> Option<State> {
+    let (state_path, legacy_state_path) = state_paths().ok()?;
+    let now = now_unix();
+    [state_path, legacy_state_path].iter().find_map(|path| {
+        let (state, mtime) = read_state(path)?;
+        let counting_down = state.paused.is_none() && state.end_ts > now;
+        let fresh = counting_down && boot_time(now).is_some_and(|boot| mtime >= boot);
+        (fresh || identity::daemon_running(&state)).then_some(state)
+    })
+}
+
+/// The state at `path` and when it was last written.
+fn read_state(path: &Path) -> Option<(State, i64)> {
+    let mut file = File::open(path).ok()?;
+    let mtime = file
+        .metadata()
+        .ok()?
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs() as i64;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).ok()?;
+    let state = serde_json::from_slice(&contents).ok()?;
+    Some((state, mtime))
+}
+
+/// When the system booted. A state file older than that is left over from
+/// before a reboot.
+fn boot_time(now: i64) -> Option<i64> {
+    #[cfg(target_os = "linux")]
+    const CLOCK: libc::clockid_t = libc::CLOCK_BOOTTIME;
+    #[cfg(not(target_os = "linux"))]
+    const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC;
+
+    let mut uptime: libc::timespec = unsafe { std::mem::zeroed() };
+    if unsafe { libc::clock_gettime(CLOCK, &mut uptime) } == -1 {
+        return None;
+    }
+    Some(now - uptime.tv_sec as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequence::Sequence;
+    use crate::tests::running;
+    use crate::Pause;
+
+    fn fast_path(args: &[&str]) -> Option<String> {
+        fast_path_format(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn fast_path_takes_only_plain_prompt_invocations() {
+        assert_eq!(
+            fast_path(&["tock", "prompt"]),
+            Some(DEFAULT_FORMAT.to_string())
+        );
+        assert_eq!(
+            fast_path(&["tock", "prompt", "--format", "{mode}"]),
+            Some("{mode}".to_string())
+        );
+        assert_eq!(
+            fast_path(&["tock", "prompt", "--format={mode}"]),
+            Some("{mode}".to_string())
+        );
+        assert_eq!(
+            fast_path(&["tock", "prompt", "--format="]),
+            Some(String::new())
+        );
+
+        for args in [
+            &["tock"][..],
+            &["tock", "status"],
+            &["tock", "prompt", "init", "bash"],
+            &["tock", "prompt", "--help"],
+            &["tock", "prompt", "--format"],
+            &["tock", "prompt", "--formt={mode}"],
+            &["tock", "prompt", "--format", "{mode}", "extra"],
+            &["tock", "prompt", "--format={mode}", "--format={icon}"],
+            &["tock", "--verbose", "prompt"],
+        ] {
+            assert_eq!(fast_path(args), None, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn render_fills_in_placeholders() {
+        let format = "{icon} {mode} {remaining} [{set}] {task} {other}";
+        let mut state = running(Mode::Work, 1000, 1500);
+        state.meta.task = Some("{mode}".to_string());
+        assert_eq!(
+            render(format, &state, 1300),
+            "🍅 work 20:00 [] {mode} {other}"
+        );
+
+        state.sequence = Some(Sequence::parse("(25w 5b)x3").unwrap());
+        state.meta.task = None;
+        state.paused = Some(Pause {
+            since: 1300,
+            remaining_secs: 1200,
+        });
+        assert_eq!(render(format, &state, 2000), "⏸ work 20:00 [1/3]  {other}");
+
+        let mut state = running(Mode::Break, 1000, 300);
+        state.scheduled = true;
+        assert_eq!(render(format, &state, 940), "⏳ break 01:00 []  {other}");
+
+        let mut state = running(Mode::LongBreak, 1000, 900);
+        state.awaiting_ack = true;
+        assert_eq!(
+            render(format, &state, 2092),
+            "🌴 long break +03:12 []  {other}"
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! Machine-readable output for `status`, `start` and `stop`.
+//!
+//! The JSON object carries a `version` that is bumped whenever a field changes
+//! meaning or goes away; new fields may be added without a bump. `kv` prints
+//! the same fields as `key=value` lines that a shell can `eval`.
+
+use crate::goal::Progress;
+use crate::sequence::{Sequence, Step};
+use crate::{format_rfc3339, Mode, State};
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+
+const REPORT_VERSION: u32 = 1;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    #[default]
+    Text,
+    Json,
+    Kv,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct OutputArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Shorthand for --format json
+    #[arg(long, conflicts_with = "format")]
+    json: bool,
+}
+
+impl OutputArgs {
+    pub(crate) fn format(&self) -> Format {
+        if self.json {
+            Format::Json
+        } else {
+            self.format
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RunState {
+    Running,
+    Paused,
+    /// Waiting for its start time (`start --at` / `--in`).
+    Scheduled,
+    /// Over, and waiting for `tock next` to start the next phase.
+    AwaitingAck,
+    NotRunning,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Started,
+    Stopped,
+    /// `stop` called off a scheduled start.
+    Cancelled,
+    /// `stop` found nothing to stop.
+    None,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action: Option<Action>,
+    state: RunState,
+    /// The current session, if one is running.
+    #[serde(flatten)]
+    session: Option<SessionReport>,
+    /// The session `stop` ended.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stopped: Option<SessionReport>,
+    /// Today's pomodoros against the daily goal, if one is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    goal: Option<Progress>,
+}
+
+impl Report {
+    /// What `status` shows: the live session, or nothing.
+    pub(crate) fn status(state: Option<&State>, now: i64) -> Result<Report, String> {
+        let session = state
+            .map(|state| SessionReport::new(state, now))
+            .transpose()?;
+        Ok(Report {
+            version: REPORT_VERSION,
+            action: None,
+            state: run_state(state),
+            session,
+            stopped: None,
+            goal: None,
+        })
+    }
+
+    pub(crate) fn with_goal(self, goal: Option<Progress>) -> Report {
+        Report { goal, ..self }
+    }
+
+    pub(crate) fn started(state: &State, now: i64) -> Result<Report, String> {
+        Ok(Report {
+            action: Some(Action::Started),
+            ..Report::status(Some(state), now)?
+        })
+    }
+
+    /// `stopped` is the session that was stopped, if there was one.
+    pub(crate) fn stopped(stopped: Option<&State>, now: i64) -> Result<Report, String> {
+        let action = match stopped {
+            Some(state) if state.scheduled => Action::Cancelled,
+            Some(_) => Action::Stopped,
+            None => Action::None,
+        };
+        Ok(Report {
+            version: REPORT_VERSION,
+            action: Some(action),
+            state: RunState::NotRunning,
+            session: None,
+            stopped: stopped
+                .map(|state| SessionReport::new(state, now))
+                .transpose()?,
+            goal: None,
+        })
+    }
+
+    /// Prints the report as JSON or `key=value` lines. Text output is up to the caller.
+    pub(crate) fn print(&self, format: Format) -> Result<(), String> {
+        let serialize_err = |e: serde_json::Error| format!("failed to serialize report: {e}");
+        match format {
+            Format::Json => println!("{0}", serde_json::to_string(self).map_err(serialize_err)?),
+            Format::Kv => {
+                for line in self.kv_lines().map_err(serialize_err)? {
+                    println!("{line}");
+                }
+            }
+            Format::Text => {}
+        }
+        Ok(())
+    }
+
+    fn kv_lines(&self) -> Result<Vec<String>, serde_json::Error> {
+        let mut lines = Vec::new();
+        kv_lines("", &serde_json::to_value(self)?, &mut lines);
+        Ok(lines)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SessionReport {
+    mode: Mode,
+    pid: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    set: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sets: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    steps: Option<usize>,
+    /// The phase after this one in a sequence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<Step>,
+    /// When a scheduled session is due to begin.
+    start_ts: i64,
+    start: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    starts_in_secs: Option<u64>,
+    /// Unknown while paused; planned while scheduled.
+    end_ts: Option<i64>,
+    end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    paused_since_ts: Option<i64>,
+    remaining_secs: u64,
+    /// How long ago the phase ran out, while it waits for `tock next`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overdue_secs: Option<u64>,
+    progress: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+impl SessionReport {
+    fn new(state: &State, now: i64) -> Result<SessionReport, String> {
+        let end_ts = state.paused.is_none().then_some(state.end_ts);
+
+        Ok(SessionReport {
+            mode: state.mode,
+            pid: state.pid,
+            set: state.sequence.as_ref().map(Sequence::set),
+            sets: state.sequence.as_ref().map(Sequence::sets),
+            step: state.sequence.as_ref().map(Sequence::step),
+            steps: state.sequence.as_ref().map(Sequence::steps),
+            next: state.sequence.as_ref().and_then(Sequence::next),
+            start_ts: state.start_ts,
+            start: format_rfc3339(state.start_ts)?,
+            starts_in_secs: state.starts_in(now),
+            end_ts,
+            end: end_ts.map(format_rfc3339).transpose()?,
+            paused_since_ts: state.paused.map(|pause| pause.since),
+            remaining_secs: state.remaining_secs(now),
+            overdue_secs: state.overdue_secs(now),
+            progress: state.progress(now),
+            task: state.meta.task.clone(),
+            tags: state.meta.tags.clone(),
+            note: state.meta.note.clone(),
+        })
+    }
+}
+
+fn run_state(state: Option<&State>) -> RunState {
+    match state {
+        Some(state) if state.paused.is_some() => RunState::Paused,
+        Some(state) if state.scheduled => RunState::Scheduled,
+        Some(state) if state.awaiting_ack => RunState::AwaitingAck,
+        Some(_) => RunState::Running,
+        None => RunState::NotRunning,
+    }
+}
+
+/// Flattens `value` into `key=value` lines. Nested objects get their key as a
+/// prefix, arrays are comma-joined and nulls are left out.
+fn kv_lines(prefix: &str, value: &Value, lines: &mut Vec<String>) {
+    let scalar = match value {
+        Value::Null => return,
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}_{key}")
+                };
+                kv_lines(&key, value, lines);
+            }
+            return;
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    lines.push(format!("{prefix}={0}", shell_word(&scalar)));
+}
+
+/// Quotes `s` for a POSIX shell unless it is plainly safe as is.
+fn shell_word(s: &str) -> String {
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.,:/+@%".contains(c));
+    if safe {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{in_tz, running, CET, JAN_10_10AM};
+    use crate::Pause;
+
+    fn snapshot(report: &Report) -> (String, Vec<String>) {
+        (
+            serde_json::to_string(report).unwrap(),
+            report.kv_lines().unwrap(),
+        )
+    }
+
+    #[test]
+    fn status_reports_a_running_session() {
+        in_tz(CET, || {
+            let mut state = running(Mode::Work, JAN_10_10AM, 1500);
+            state.meta.task = Some("write it up".to_string());
+            state.meta.tags = vec!["a".to_string(), "b".to_string()];
+            let report = Report::status(Some(&state), JAN_10_10AM + 300).unwrap();
+            let (json, kv) = snapshot(&report);
+            assert_eq!(
+                json,
+                r#"{"version":1,"state":"running","mode":"work","pid":42,"start_ts":1768035600,"start":"2026-01-10T10:00:00+01:00","end_ts":1768037100,"end":"2026-01-10T10:25:00+01:00","remaining_secs":1200,"progress":0.2,"task":"write it up","tags":["a","b"]}"#
+            );
+            assert_eq!(
+                kv,
+                [
+                    "end=2026-01-10T10:25:00+01:00",
+                    "end_ts=1768037100",
+                    "mode=work",
+                    "pid=42",
+                    "progress=0.2",
+                    "remaining_secs=1200",
+                    "start=2026-01-10T10:00:00+01:00",
+                    "start_ts=1768035600",
+                    "state=running",
+                    "tags=a,b",
+                    "task='write it up'",
+                    "version=1",
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn status_reports_a_paused_session() {
+        in_tz(CET, || {
+            let mut state = running(Mode::Break, JAN_10_10AM, 300);
+            state.paused = Some(Pause {
+                since: JAN_10_10AM + 60,
+                remaining_secs: 240,
+            });
+            let report = Report::status(Some(&state), JAN_10_10AM + 120).unwrap();
+            let (json, kv) = snapshot(&report);
+            assert_eq!(
+                json,
+                r#"{"version":1,"state":"paused","mode":"break","pid":42,"start_ts":1768035600,"start":"2026-01-10T10:00:00+01:00","end_ts":null,"end":null,"paused_since_ts":1768035660,"remaining_secs":240,"progress":0.2}"#
+            );
+            assert_eq!(
+                kv,
+                [
+                    "mode=break",
+                    "paused_since_ts=1768035660",
+                    "pid=42",
+                    "progress=0.2",
+                    "remaining_secs=240",
+                    "start=2026-01-10T10:00:00+01:00",
+                    "start_ts=1768035600",
+                    "state=paused",
+                    "version=1",
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn stop_reports_nothing_stopped() {
+        let (json, kv) = snapshot(&Report::stopped(None, JAN_10_10AM).unwrap());
+        assert_eq!(
+            json,
+            r#"{"version":1,"action":"none","state":"not_running"}"#
+        );
+        assert_eq!(kv, ["action=none", "state=not_running", "version=1"]);
+        let (json, kv) = snapshot(&Report::status(None, JAN_10_10AM).unwrap());
+        assert_eq!(json, r#"{"version":1,"state":"not_running"}"#);
+        assert_eq!(kv, ["state=not_running", "version=1"]);
+    }
+}
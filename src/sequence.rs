@@ -0,0 +1,555 @@
+//! Sequences of phases, e.g. `10w (25w 5b)x3 25w 15lb`.
+//!
+//! A step is a length followed by `w` (work), `b` (break) or `lb` (long
+//! break); the length reads as anywhere else (`25`, `90s`, `1h15m`, `25:00`).
+//! A group in parentheses followed by `xN` is repeated N times. The daemon
+//! runs the steps in order and is done after the last one; with
+//! `--manual-advance` it waits for `tock next` before each step after the
+//! first. A `--sets` cycle is a sequence too.
+
+use crate::config::{self, SequenceConfig, SessionConfig};
+use crate::report::OutputArgs;
+use crate::{
+    config_path, format_length, parse_duration, start_session, LongBreak, Meta, Mode, ScheduleArgs,
+};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Most steps a sequence may have.
+const MAX_STEPS: usize = 200;
+/// Most groups a sequence may have one inside another.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Args, Debug)]
+pub(crate) struct RunSequenceArgs {
+    /// Steps such as "10w (25w 5b)x4 15lb", or the name of a `[sequence]` in the config file
+    sequence: String,
+    /// Wait for `tock next` at the end of each phase instead of starting the next one
+    #[arg(long)]
+    manual_advance: bool,
+    #[command(flatten)]
+    schedule: ScheduleArgs,
+    #[command(flatten)]
+    meta: Meta,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct Step {
+    pub(crate) mode: Mode,
+    pub(crate) secs: u64,
+}
+
+impl Step {
+    fn suffix(self) -> &'static str {
+        match self.mode {
+            Mode::Work => "w",
+            Mode::Break => "b",
+            Mode::LongBreak => "lb",
+        }
+    }
+}
+
+/// The step as written in a sequence: `25w`, `4m30sb`.
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.secs.is_multiple_of(60) {
+            write!(f, "{0}{1}", self.secs / 60, self.suffix())
+        } else {
+            write!(f, "{0}{1}", format_length(self.secs), self.suffix())
+        }
+    }
+}
+
+/// The phases a timer runs through and the one it is in.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "SequenceRecord")]
+pub(crate) struct Sequence {
+    steps: Vec<Step>,
+    index: usize,
+    /// Each step after the first waits for `tock next` instead of following
+    /// the one before by itself.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    manual_advance: bool,
+}
+
+#[derive(Deserialize)]
+struct SequenceRecord {
+    steps: Vec<Step>,
+    index: usize,
+    #[serde(default)]
+    manual_advance: bool,
+}
+
+impl TryFrom<SequenceRecord> for Sequence {
+    type Error = String;
+
+    fn try_from(record: SequenceRecord) -> Result<Sequence, String> {
+        if record.index >= record.steps.len() {
+            return Err(format!("sequence step {0} out of range", record.index + 1));
+        }
+        Ok(Sequence {
+            steps: record.steps,
+            index: record.index,
+            manual_advance: record.manual_advance,
+        })
+    }
+}
+
+impl Sequence {
+    /// Reads a sequence such as `10w (25w 5b)x4 15lb`, positioned at its first step.
+    pub(crate) fn parse(s: &str) -> Result<Sequence, String> {
+        let spaced = s.replace('(', " ( ").replace(')', " ) ");
+        let mut tokens = spaced.split_whitespace().peekable();
+        let steps = parse_steps(&mut tokens, 0)?;
+        if let Some(token) = tokens.next() {
+            return Err(format!("unexpected {token:?} in sequence"));
+        }
+        if steps.is_empty() {
+            return Err("sequence is empty".to_string());
+        }
+        Ok(Sequence {
+            steps,
+            index: 0,
+            manual_advance: false,
+        })
+    }
+
+    /// `sets` work sessions with breaks between them, positioned at the first.
+    pub(crate) fn cycle(
+        sets: u64,
+        work_secs: u64,
+        break_secs: u64,
+        long_break: Option<LongBreak>,
+    ) -> Sequence {
+        let mut steps = Vec::new();
+        for set in 1..=sets {
+            steps.push(Step {
+                mode: Mode::Work,
+                secs: work_secs,
+            });
+            let last = set == sets;
+            let step = match long_break {
+                Some(long_break) if last && long_break.at_end => Step {
+                    mode: Mode::LongBreak,
+                    secs: long_break.secs,
+                },
+                _ if last => break,
+                Some(long_break) if set.is_multiple_of(long_break.every) => Step {
+                    mode: Mode::LongBreak,
+                    secs: long_break.secs,
+                },
+                _ => Step {
+                    mode: Mode::Break,
+                    secs: break_secs,
+                },
+            };
+            steps.push(step);
+        }
+        Sequence {
+            steps,
+            index: 0,
+            manual_advance: false,
+        }
+    }
+
+    /// The same steps, positioned at `step` (1-based).
+    pub(crate) fn at_step(self, step: usize) -> Result<Sequence, String> {
+        if step == 0 || step > self.steps.len() {
+            return Err(format!("step must be 1 to {0}", self.steps.len()));
+        }
+        Ok(Sequence {
+            index: step - 1,
+            ..self
+        })
+    }
+
+    /// The same steps, waiting for `tock next` between them if `manual_advance`.
+    pub(crate) fn with_manual_advance(self, manual_advance: bool) -> Sequence {
+        Sequence {
+            manual_advance,
+            ..self
+        }
+    }
+
+    pub(crate) fn manual_advance(&self) -> bool {
+        self.manual_advance
+    }
+
+    pub(crate) fn current(&self) -> Step {
+        self.steps[self.index]
+    }
+
+    pub(crate) fn next(&self) -> Option<Step> {
+        self.steps.get(self.index + 1).copied()
+    }
+
+    /// The sequence moved on to its next step; `None` after the last.
+    pub(crate) fn advance(&self) -> Option<Sequence> {
+        self.next()?;
+        Some(Sequence {
+            steps: self.steps.clone(),
+            index: self.index + 1,
+            manual_advance: self.manual_advance,
+        })
+    }
+
+    /// The current step, counting from 1.
+    pub(crate) fn step(&self) -> usize {
+        self.index + 1
+    }
+
+    pub(crate) fn steps(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Work steps up to and including the current one: the set a cycle is in.
+    pub(crate) fn set(&self) -> u64 {
+        work_steps(&self.steps[..=self.index])
+    }
+
+    /// Work steps in the whole sequence.
+    pub(crate) fn sets(&self) -> u64 {
+        work_steps(&self.steps)
+    }
+
+    /// E.g. `(25w 5b)x3 25w, 1h45m in all`.
+    pub(crate) fn summary(&self) -> String {
+        let total_secs = self.steps.iter().map(|step| step.secs).sum();
+        let advance = if self.manual_advance {
+            ", advancing on `tock next`"
+        } else {
+            ""
+        };
+        format!("{self}, {0} in all{advance}", format_length(total_secs))
+    }
+}
+
+fn work_steps(steps: &[Step]) -> u64 {
+    steps.iter().filter(|step| step.mode == Mode::Work).count() as u64
+}
+
+/// The steps, with runs that repeat written as `(…)xN`; `Sequence::parse`
+/// reads it back.
+impl fmt::Display for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        let mut i = 0;
+        while i < self.steps.len() {
+            let (period, times) = longest_repeat(&self.steps[i..]);
+            let group: Vec<String> = self.steps[i..i + period]
+                .iter()
+                .map(Step::to_string)
+                .collect();
+            if times > 1 {
+                parts.push(format!("({0})x{times}", group.join(" ")));
+            } else {
+                parts.push(group.join(" "));
+            }
+            i += period * times;
+        }
+        write!(f, "{0}", parts.join(" "))
+    }
+}
+
+/// The group at the start of `steps` whose back-to-back repeats cover the most
+/// steps, as (group length, repeats).
+fn longest_repeat(steps: &[Step]) -> (usize, usize) {
+    let mut best = (1, 1);
+    for period in 1..=steps.len() / 2 {
+        let group = &steps[..period];
+        let times = steps
+            .chunks_exact(period)
+            .take_while(|chunk| *chunk == group)
+            .count();
+        if times > 1 && period * times > best.0 * best.1 {
+            best = (period, times);
+        }
+    }
+    best
+}
+
+fn parse_steps<'a>(
+    tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+    depth: usize,
+) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    while let Some(&token) = tokens.peek() {
+        if token == ")" {
+            if depth == 0 {
+                return Err("unmatched ')' in sequence".to_string());
+            }
+            break;
+        }
+        tokens.next();
+        if token == "(" {
+            if depth == MAX_DEPTH {
+                return Err(format!(
+                    "sequence nested too deeply (max {MAX_DEPTH} levels)"
+                ));
+            }
+            let group = parse_steps(tokens, depth + 1)?;
+            if tokens.next() != Some(")") {
+                return Err("unclosed '(' in sequence".to_string());
+            }
+            let times = match tokens.peek().and_then(|token| token.strip_prefix('x')) {
+                Some(times) => {
+                    tokens.next();
+                    times
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&times| times > 0)
+                        .ok_or_else(|| format!("invalid repeat count {times:?} in sequence"))?
+                }
+                None => 1,
+            };
+            if group.len().saturating_mul(times) > MAX_STEPS {
+                return Err(format!("sequence too long (max {MAX_STEPS} steps)"));
+            }
+            for _ in 0..times {
+                steps.extend_from_slice(&group);
+            }
+        } else {
+            steps.push(parse_step(token)?);
+        }
+        if steps.len() > MAX_STEPS {
+            return Err(format!("sequence too long (max {MAX_STEPS} steps)"));
+        }
+    }
+    Ok(steps)
+}
+
+fn parse_step(token: &str) -> Result<Step, String> {
+    let (length, mode) = if let Some(length) = token.strip_suffix("lb") {
+        (length, Mode::LongBreak)
+    } else if let Some(length) = token.strip_suffix('b') {
+        (length, Mode::Break)
+    } else if let Some(length) = token.strip_suffix('w') {
+        (length, Mode::Work)
+    } else {
+        return Err(format!(
+            "invalid step {token:?} (expected a length and w, b or lb, e.g. 25w)"
+        ));
+    };
+    let secs = parse_duration(length).map_err(|e| format!("step {token:?}: {e}"))?;
+    Ok(Step { mode, secs })
+}
+
+pub(crate) fn run_sequence(args: RunSequenceArgs) -> Result<(), String> {
+    let cli = SessionConfig {
+        manual_advance: args.manual_advance.then_some(true),
+        ..SessionConfig::default()
+    };
+    let manual_advance = config::session_settings(cli, None)?
+        .manual_advance
+        .unwrap_or(false);
+    let sequence = resolve(&args.sequence)?.with_manual_advance(manual_advance);
+    let step = sequence.current();
+    start_session(
+        step.mode,
+        step.secs,
+        Some(sequence),
+        args.meta.normalized(),
+        args.schedule.starts_at()?,
+        args.output.format(),
+    )
+}
+
+/// A `[sequence]` from the config file by name, or else the steps in `spec`.
+fn resolve(spec: &str) -> Result<Sequence, String> {
+    let path = config_path()?;
+    let mut sequences = config::load(&path)?.sequence;
+    if let Some(SequenceConfig(sequence)) = sequences.remove(spec) {
+        return Ok(sequence);
+    }
+    let is_name = spec.starts_with(|c: char| c.is_ascii_alphabetic())
+        && spec
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match Sequence::parse(spec) {
+        Err(_) if is_name => Err(format!("{0}: no sequence named {spec:?}", path.display())),
+        parsed => parsed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn work(secs: u64) -> Step {
+        Step {
+            mode: Mode::Work,
+            secs,
+        }
+    }
+
+    fn short(secs: u64) -> Step {
+        Step {
+            mode: Mode::Break,
+            secs,
+        }
+    }
+
+    fn long(secs: u64) -> Step {
+        Step {
+            mode: Mode::LongBreak,
+            secs,
+        }
+    }
+
+    fn steps(s: &str) -> Result<Vec<Step>, String> {
+        Sequence::parse(s).map(|sequence| sequence.steps)
+    }
+
+    #[test]
+    fn parse_step_reads_the_length_and_kind() {
+        assert_eq!(parse_step("25w"), Ok(work(25 * 60)));
+        assert_eq!(parse_step("5b"), Ok(short(5 * 60)));
+        assert_eq!(parse_step("15lb"), Ok(long(15 * 60)));
+        assert_eq!(parse_step("1m30sw"), Ok(work(90)));
+        assert_eq!(parse_step("1:30:00w"), Ok(work(90 * 60)));
+        assert!(parse_step("25")
+            .unwrap_err()
+            .starts_with("invalid step \"25\""));
+        assert!(parse_step("w").unwrap_err().starts_with("step \"w\": "));
+        assert!(parse_step("0b").unwrap_err().starts_with("step \"0b\": "));
+    }
+
+    #[test]
+    fn parse_repeats_groups() {
+        assert_eq!(
+            steps("10w (25w 5b)x2 15lb"),
+            Ok(vec![
+                work(600),
+                work(1500),
+                short(300),
+                work(1500),
+                short(300),
+                long(900)
+            ])
+        );
+        assert_eq!(steps("(25w)"), Ok(vec![work(1500)]));
+        assert_eq!(
+            steps("((1w 1b)x2 2lb)x2"),
+            Ok([work(60), short(60), work(60), short(60), long(120)].repeat(2))
+        );
+        assert_eq!(steps("(1w)x2(1b)x2"), steps("1w 1w 1b 1b"));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_sequences() {
+        assert_eq!(steps(""), Err("sequence is empty".to_string()));
+        assert_eq!(steps("()x3"), Err("sequence is empty".to_string()));
+        assert_eq!(steps("25w )"), Err("unmatched ')' in sequence".to_string()));
+        assert_eq!(
+            steps("(25w 5b"),
+            Err("unclosed '(' in sequence".to_string())
+        );
+        assert_eq!(
+            steps("(25w)x0"),
+            Err("invalid repeat count \"0\" in sequence".to_string())
+        );
+        assert_eq!(
+            steps("(25w)xx"),
+            Err("invalid repeat count \"x\" in sequence".to_string())
+        );
+        assert_eq!(
+            steps("(25w) 3"),
+            Err("invalid step \"3\" (expected a length and w, b or lb, e.g. 25w)".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_limits_the_number_of_steps() {
+        let too_long = format!("sequence too long (max {MAX_STEPS} steps)");
+        assert_eq!(
+            steps(&format!("(1w)x{MAX_STEPS}")).unwrap().len(),
+            MAX_STEPS
+        );
+        assert_eq!(
+            steps(&format!("(1w)x{MAX_STEPS} 1b")),
+            Err(too_long.clone())
+        );
+        assert_eq!(
+            steps(&format!("(1w 1b)x{MAX_STEPS}")),
+            Err(too_long.clone())
+        );
+        assert_eq!(
+            steps("((((1w)x1000)x1000)x1000)x1000"),
+            Err(too_long.clone())
+        );
+        assert_eq!(steps(&format!("(1w)x{}", usize::MAX)), Err(too_long));
+        let one_step = "1w ".repeat(MAX_STEPS + 1);
+        assert!(steps(&one_step).is_err());
+    }
+
+    #[test]
+    fn parse_limits_nesting() {
+        let nested = |depth: usize| format!("{0}1w{1}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(steps(&nested(MAX_DEPTH)), Ok(vec![work(60)]));
+        let too_deep = Err(format!(
+            "sequence nested too deeply (max {MAX_DEPTH} levels)"
+        ));
+        assert_eq!(steps(&nested(MAX_DEPTH + 1)), too_deep);
+        assert_eq!(steps(&nested(100_000)), too_deep);
+        assert_eq!(steps(&"(".repeat(100_000)), too_deep);
+        assert_eq!(steps("((1w)x2 (1b)x2)x2").unwrap().len(), 8);
+    }
+
+    #[test]
+    fn display_reads_back_as_the_same_steps() {
+        for s in [
+            "25w",
+            "1m30sw 45sb",
+            "10w (25w 5b)x3 25w 15lb",
+            "(25w 5b 25w 15lb)x2",
+            "((1w 1b)x2 2lb)x3 1w",
+            "1h15mw 1lb 1lb 1w",
+        ] {
+            let sequence = Sequence::parse(s).unwrap();
+            let shown = sequence.to_string();
+            assert_eq!(
+                Sequence::parse(&shown),
+                Ok(sequence),
+                "{s} shown as {shown}"
+            );
+        }
+    }
+
+    #[test]
+    fn display_writes_repeats_as_groups() {
+        let shown = |s: &str| Sequence::parse(s).unwrap().to_string();
+        assert_eq!(shown("25w 5b 25w 5b 25w 5b 25w"), "(25w 5b)x3 25w");
+        assert_eq!(shown("1m30sw 1m30sw"), "(1m30sw)x2");
+        assert_eq!(shown("1w 2w 1w 2w 1w 2w"), "(1w 2w)x3");
+        assert_eq!(shown("1w 1w 2w 1w 1w 2w"), "(1w 1w 2w)x2");
+        assert_eq!(shown("15lb 15lb"), "(15lb)x2");
+    }
+
+    #[test]
+    fn longest_repeat_prefers_the_most_covered_steps() {
+        let (a, b) = (work(60), short(60));
+        assert_eq!(longest_repeat(&[a]), (1, 1));
+        assert_eq!(longest_repeat(&[a, b]), (1, 1));
+        assert_eq!(longest_repeat(&[a, a, b]), (1, 2));
+        assert_eq!(longest_repeat(&[a, b, a, b, a]), (2, 2));
+        assert_eq!(longest_repeat(&[a, a, a, a]), (1, 4));
+        assert_eq!(longest_repeat(&[a, a, b, a, a, b]), (3, 2));
+    }
+
+    #[test]
+    fn cycle_places_long_breaks() {
+        let long_break = LongBreak {
+            secs: 900,
+            every: 2,
+            at_end: true,
+        };
+        let cycle = Sequence::cycle(3, 1500, 300, Some(long_break));
+        assert_eq!(cycle.to_string(), "25w 5b (25w 15lb)x2");
+        assert_eq!(cycle.sets(), 3);
+        let cycle = Sequence::cycle(2, 1500, 300, None);
+        assert_eq!(cycle.to_string(), "25w 5b 25w");
+    }
+}
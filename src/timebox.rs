@@ -0,0 +1,371 @@
+//! `tock box`: a command run under a work session.
+//!
+//! The command runs in a process group of its own, in the foreground of the
+//! terminal. A daemon keeps the session as it would for `tock start`, so
+//! `status`, the bars and `extend` see and change it, and `tock box` follows
+//! it on the control socket: it warns before the end, announces the end, and
+//! once the grace period is over sends the command's group the chosen signal,
+//! then SIGKILL if another grace period goes by. A session stopped, skipped or
+//! replaced from elsewhere gets the signal straight away. When the command
+//! exits, the session is logged with its exit status and `tock box` exits with
+//! the same code. Notifications go out from a thread of their own, so a slow
+//! backend never holds up the signal.
+
+use crate::control::{self, Request};
+use crate::notify::Notification;
+use crate::{
+    control_socket_path, controlling_tty, format_length, format_start_time, notify, now_unix,
+    parse_duration, parse_span, spawn_session, Meta, Mode, State, MAX_MINUTES,
+};
+use clap::Args;
+use std::ffi::OsString;
+use std::io;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// Signals `--signal` takes by name.
+const SIGNALS: [(&str, i32); 7] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+];
+
+#[derive(Args, Debug)]
+pub(crate) struct TimeboxArgs {
+    /// Time the command gets, e.g. 30m or 1h
+    #[arg(value_parser = parse_duration)]
+    length: u64,
+    /// Notify when this much time is left, e.g. 5m
+    #[arg(long, value_parser = parse_duration)]
+    warn_at: Option<u64>,
+    /// How long the command may run on after time is up before it gets the signal
+    #[arg(long, value_parser = parse_grace, default_value = "10s")]
+    grace: u64,
+    /// Signal to send when the grace period is over: a name such as TERM, INT or HUP, or a number
+    #[arg(long, value_parser = parse_signal, default_value = "TERM")]
+    signal: i32,
+    #[command(flatten)]
+    meta: Meta,
+    /// The command to run and its arguments
+    #[arg(last = true, required = true, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+enum Event {
+    /// The session changed; `None` once it is over.
+    Timer(Option<Box<State>>),
+    Exited(io::Result<ExitStatus>),
+}
+
+/// Runs the command and returns the exit code to leave with.
+pub(crate) fn timebox(args: TimeboxArgs) -> Result<i32, String> {
+    let TimeboxArgs {
+        length,
+        warn_at,
+        grace,
+        signal,
+        meta,
+        command,
+    } = args;
+    if warn_at.is_some_and(|warn_at| warn_at >= length) {
+        return Err("--warn-at must be shorter than the box".to_string());
+    }
+    let label = command
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut meta = meta.normalized();
+    if meta.task.is_none() {
+        meta.task = Some(label.clone());
+    }
+
+    let box_pid = std::process::id() as i32;
+    let state = spawn_session(Mode::Work, length, None, meta, None, Some(box_pid))?;
+    let socket_path = control_socket_path(state.pid)?;
+    eprintln!(
+        "tock: {label} has until {0} (pid {1})",
+        format_start_time(state.end_ts)?,
+        state.pid
+    );
+
+    let take_terminal = owns_terminal();
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::setpgid(0, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            if take_terminal {
+                set_foreground(libc::getpid());
+            }
+            Ok(())
+        });
+    }
+    let child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("tock: failed to run {label}: {e}");
+            // What a shell exits with for a command it can't find or run.
+            let code = if e.kind() == io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+            end_session(&socket_path, code);
+            return Ok(code);
+        }
+    };
+    let pgid = child.id() as i32;
+    // The child does this too; whichever runs first, the group exists before
+    // it can be signalled.
+    unsafe {
+        libc::setpgid(pgid, pgid);
+    }
+
+    let (events, received) = mpsc::channel();
+    let exited = events.clone();
+    thread::spawn(move || {
+        let _ = exited.send(Event::Exited(wait(pgid, take_terminal)));
+    });
+    match control::subscribe(&socket_path) {
+        Ok(mut subscription) => {
+            thread::spawn(move || loop {
+                let state = subscription
+                    .next()
+                    .ok()
+                    .flatten()
+                    .and_then(|response| response.state.map(Box::new));
+                let done = state.is_none();
+                if events.send(Event::Timer(state)).is_err() || done {
+                    break;
+                }
+            });
+        }
+        Err(e) => eprintln!("warning: {e}"),
+    }
+
+    let (notifications, to_notify) = mpsc::channel::<Notification>();
+    let notifier = thread::spawn(move || {
+        let tty = controlling_tty();
+        for notification in to_notify {
+            notify(&notification, tty.as_deref());
+        }
+    });
+    let send = |signal: i32| {
+        eprintln!("tock: sending {0} to {label}", signal_name(signal));
+        unsafe {
+            libc::kill(-pgid, signal);
+        }
+    };
+    let mut timer = Some(state);
+    let mut warned = false;
+    let mut announced = false;
+    // When the signal went out, and whether SIGKILL followed.
+    let mut signalled: Option<i64> = None;
+    let mut killed = false;
+    let status = loop {
+        let now = now_unix();
+        // The next time something is due; `None` waits for the session or the command.
+        let wake = match (&timer, signalled) {
+            (_, Some(_)) if killed => None,
+            (_, Some(at)) if now >= at + grace as i64 => {
+                send(libc::SIGKILL);
+                killed = true;
+                None
+            }
+            (_, Some(at)) => Some(at + grace as i64),
+            (None, None) => {
+                send(signal);
+                signalled = Some(now);
+                continue;
+            }
+            (Some(state), None) if state.paused.is_some() => None,
+            (Some(state), None) => {
+                let end_ts = state.end_ts;
+                let warn_ts = warn_at.map(|warn_at| end_ts - warn_at as i64);
+                // `extend` can give back time that was warned about or up.
+                if warn_ts.is_some_and(|warn_ts| now < warn_ts) {
+                    warned = false;
+                }
+                if now < end_ts {
+                    announced = false;
+                }
+                if warn_ts.is_some_and(|warn_ts| now >= warn_ts) && now < end_ts && !warned {
+                    let left = format_length((end_ts - now) as u64);
+                    let _ = notifications.send(Notification::timebox_warning(&label, &left));
+                    warned = true;
+                }
+                if now >= end_ts && !announced {
+                    let then = if grace == 0 {
+                        format!("Sending {0} now.", signal_name(signal))
+                    } else {
+                        format!(
+                            "Sending {0} in {1}.",
+                            signal_name(signal),
+                            format_length(grace)
+                        )
+                    };
+                    let _ = notifications.send(Notification::timebox_over(&label, &then));
+                    announced = true;
+                }
+                if now >= end_ts + grace as i64 {
+                    send(signal);
+                    signalled = Some(now);
+                    continue;
+                }
+                match warn_ts {
+                    Some(warn_ts) if now < warn_ts => Some(warn_ts),
+                    _ if now < end_ts => Some(end_ts),
+                    _ => Some(end_ts + grace as i64),
+                }
+            }
+        };
+        let event = match wake {
+            Some(wake) => {
+                let timeout = Duration::from_secs((wake - now).max(1) as u64);
+                match received.recv_timeout(timeout) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break Err(disconnected()),
+                }
+            }
+            None => match received.recv() {
+                Ok(event) => event,
+                Err(_) => break Err(disconnected()),
+            },
+        };
+        match event {
+            Event::Timer(state) => timer = timer.and(state.map(|state| *state)),
+            Event::Exited(status) => break status,
+        }
+    };
+    if take_terminal {
+        unsafe {
+            set_foreground(libc::getpgrp());
+        }
+    }
+    // Let a notification already on its way finish.
+    drop(notifications);
+    let _ = notifier.join();
+
+    let status = status.map_err(|e| format!("failed to wait for {label}: {e}"))?;
+    let code = exit_code(status);
+    // Stopped or replaced from elsewhere, the session has already been logged.
+    if timer.is_some() {
+        end_session(&socket_path, code);
+    }
+    Ok(code)
+}
+
+/// Waits for the command to exit. Ctrl-Z stops the command, since it has the
+/// terminal; tock then takes the terminal back and stops its own group too, so
+/// the shell sees the job stopped. `fg` continues tock, which hands the
+/// terminal back to the command and continues it.
+fn wait(pid: i32, take_terminal: bool) -> io::Result<ExitStatus> {
+    loop {
+        let mut status = 0;
+        if unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) } == -1 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+        if !libc::WIFSTOPPED(status) {
+            return Ok(ExitStatus::from_raw(status));
+        }
+        // Stopped by something other than the terminal, it is left to whoever stopped it.
+        if !take_terminal {
+            continue;
+        }
+        unsafe {
+            set_foreground(libc::getpgrp());
+            // Returns once continued, or at once if no shell is left to do it.
+            libc::kill(0, libc::SIGTSTP);
+            set_foreground(pid);
+            libc::kill(-pid, libc::SIGCONT);
+        }
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::other("lost track of the command")
+}
+
+/// Tells the daemon the command exited, which ends and logs the session.
+fn end_session(socket_path: &std::path::Path, code: i32) {
+    let response = control::request(socket_path, &Request::Exited { status: code });
+    match response {
+        Ok(response) => {
+            if let Some(e) = response.error {
+                eprintln!("warning: {e}");
+            }
+        }
+        Err(e) => eprintln!("warning: {e}"),
+    }
+}
+
+/// The exit code, or 128 plus the signal that killed the command, as shells report it.
+fn exit_code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// Whether tock is in the foreground of the terminal on stdin, so it can hand
+/// the terminal to the command.
+fn owns_terminal() -> bool {
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    }
+}
+
+/// Makes `pgid` the foreground process group of the terminal on stdin. A
+/// background group gets SIGTTOU for that, so it is ignored meanwhile.
+unsafe fn set_foreground(pgid: i32) {
+    let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+    libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+    libc::signal(libc::SIGTTOU, previous);
+}
+
+/// Parses `--grace`: like a session length, but 0 is allowed.
+fn parse_grace(s: &str) -> Result<u64, String> {
+    let secs = parse_span(s)?;
+    if secs > MAX_MINUTES * 60 {
+        return Err(format!("grace period too long (max {MAX_MINUTES} minutes)"));
+    }
+    Ok(secs)
+}
+
+fn parse_signal(s: &str) -> Result<i32, String> {
+    let upper = s.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    if let Ok(number) = name.parse::<i32>() {
+        if (1..=64).contains(&number) {
+            return Ok(number);
+        }
+    }
+    SIGNALS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, signal)| signal)
+        .ok_or_else(|| format!("unknown signal {s:?} (expected e.g. TERM, INT or 9)"))
+}
+
+fn signal_name(signal: i32) -> String {
+    match SIGNALS.iter().find(|&&(_, known)| known == signal) {
+        Some((name, _)) => format!("SIG{name}"),
+        None => format!("signal {signal}"),
+    }
+}
@@ -0,0 +1,183 @@
+//! `tock watch`: a live countdown for a terminal pane.
+//!
+//! The watcher only reads state.json, so it follows whatever the daemon and the
+//! other commands do to the timer. It sleeps until the next whole second between
+//! redraws and never touches the timer itself; Ctrl-C just detaches.
+
+use crate::{current_state, format_duration, now_unix, State};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BAR_WIDTH: usize = 24;
+
+static DETACHED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_detach_signal(_: libc::c_int) {
+    DETACHED.store(true, Ordering::Relaxed);
+}
+
+/// Redraws the countdown until the timer stops or the user detaches. Returns 1
+/// if no timer is running to begin with.
+pub(crate) fn watch() -> Result<i32, String> {
+    if current_state()?.is_none() {
+        println!("not running");
+        return Ok(1);
+    }
+    install_detach_handler()?;
+
+    let interactive = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+    let mut out = io::stdout();
+    if interactive {
+        // Hide the cursor so it doesn't flicker over the line being redrawn.
+        write_out(&mut out, "\x1b[?25l")?;
+    }
+
+    let result = loop {
+        if DETACHED.load(Ordering::Relaxed) {
+            break Ok("detached; the timer keeps running");
+        }
+        let state = match current_state() {
+            Ok(Some((_, state))) => state,
+            Ok(None) => break Ok("timer stopped"),
+            Err(e) => break Err(e),
+        };
+        let now = now_unix();
+        let line = render(&state, now);
+        let frame = if interactive {
+            // A line that wraps can't be redrawn in place, so cut it to fit.
+            let line: String = line.chars().take(terminal_columns() - 1).collect();
+            format!("\r\x1b[2K{line}\x1b]0;{0}\x07", title(&state, now))
+        } else {
+            format!("{line}\n")
+        };
+        if let Err(e) = write_out(&mut out, &frame) {
+            break Err(e);
+        }
+        sleep_until_next_second();
+    };
+
+    if interactive {
+        // Show the cursor again and clear the title we set.
+        write_out(&mut out, "\x1b[?25h\x1b]0;\x07\n")?;
+    }
+    let message = result?;
+    println!("{message}");
+    Ok(0)
+}
+
+/// One line such as `work 2/4 [#########---------------] 14:31 · write report`,
+/// `work 2/4 starts in 09:59 · write report` while scheduled, or `work 2/4 over
+/// by 03:12, tock next moves on · write report` while waiting for `tock next`.
+fn render(state: &State, now: i64) -> String {
+    let mut line = state.mode.to_string();
+    if let Some(sequence) = &state.sequence {
+        line.push_str(&format!(" {0}/{1}", sequence.set(), sequence.sets()));
+    }
+    if let Some(starts_in) = state.starts_in(now) {
+        line.push_str(&format!(" starts in {0}", format_duration(starts_in)));
+        if let Some(task) = &state.meta.task {
+            line.push_str(&format!(" · {task}"));
+        }
+        return line;
+    }
+    if let Some(overdue_secs) = state.overdue_secs(now) {
+        line.push_str(&format!(
+            " over by {0}, tock next moves on",
+            format_duration(overdue_secs)
+        ));
+        if let Some(task) = &state.meta.task {
+            line.push_str(&format!(" · {task}"));
+        }
+        return line;
+    }
+    let filled = ((state.progress(now) * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    line.push_str(&format!(
+        " [{0}{1}] {2}",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        format_duration(state.remaining_secs(now))
+    ));
+    if state.paused.is_some() {
+        line.push_str(" (paused)");
+    }
+    if let Some(task) = &state.meta.task {
+        line.push_str(&format!(" · {task}"));
+    }
+    line
+}
+
+fn title(state: &State, now: i64) -> String {
+    if let Some(starts_in) = state.starts_in(now) {
+        return format!(
+            "{0} until {1} - tock",
+            format_duration(starts_in),
+            state.mode
+        );
+    }
+    if let Some(overdue_secs) = state.overdue_secs(now) {
+        return format!("+{0} {1} - tock", format_duration(overdue_secs), state.mode);
+    }
+    let paused = if state.paused.is_some() {
+        " paused"
+    } else {
+        ""
+    };
+    format!(
+        "{0} {1}{paused} - tock",
+        format_duration(state.remaining_secs(now)),
+        state.mode
+    )
+}
+
+fn terminal_columns() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if res == -1 || size.ws_col == 0 {
+        80
+    } else {
+        size.ws_col as usize
+    }
+}
+
+fn write_out(out: &mut io::Stdout, s: &str) -> Result<(), String> {
+    out.write_all(s.as_bytes())
+        .and_then(|()| out.flush())
+        .map_err(|e| format!("failed to write to stdout: {e}"))
+}
+
+/// Ctrl-C (and SIGTERM) set a flag instead of killing the process, so the
+/// terminal can be restored. No `SA_RESTART`, so they also cut a sleep short.
+fn install_detach_handler() -> Result<(), String> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_detach_signal as *const () as usize;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) == -1 {
+                return Err(format!(
+                    "failed to install signal handler: {}",
+                    io::Error::last_os_error()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Blocks until the wall clock reaches the next whole second, so the countdown
+/// ticks in step with the timer's unix-second deadlines. A signal ends it early.
+fn sleep_until_next_second() {
+    let subsec_nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let nanos = 1_000_000_000 - subsec_nanos;
+    let wait = libc::timespec {
+        tv_sec: (nanos / 1_000_000_000) as libc::time_t,
+        tv_nsec: (nanos % 1_000_000_000) as libc::c_long,
+    };
+    unsafe {
+        libc::nanosleep(&wait, std::ptr::null_mut());
+    }
+}
@@ -0,0 +1,132 @@
+//! Starts many timers at once and checks that exactly one daemon is left
+//! running, the one state.json names, with every other start logged as
+//! replaced.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STARTS: usize = 16;
+
+/// Private XDG dirs, so the test never touches a real timer.
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Sandbox {
+        let dir = std::env::temp_dir().join(format!("tock-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("config/tock")).unwrap();
+        fs::create_dir_all(dir.join("runtime")).unwrap();
+        fs::write(
+            dir.join("config/tock/config.toml"),
+            "[notify]\nbackends = [\"none\"]\n",
+        )
+        .unwrap();
+        Sandbox { dir }
+    }
+
+    fn tock(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_tock"));
+        cmd.args(args)
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_RUNTIME_DIR", self.dir.join("runtime"))
+            .stdin(Stdio::null());
+        cmd
+    }
+
+    fn data_file(&self, name: &str) -> PathBuf {
+        self.dir.join("data/tock").join(name)
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = self.tock(&["stop"]).output();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The pid in `started work timer for 5 minutes (pid 1234)`.
+fn started_pid(output: &Output) -> i32 {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "start failed: {stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let pid = stdout
+        .rsplit_once("(pid ")
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(pid, _)| pid)
+        .unwrap_or_else(|| panic!("no pid in {stdout:?}"));
+    pid.parse().unwrap()
+}
+
+/// Whether `pid` is a process that hasn't exited. Daemons are re-parented when
+/// their `tock start` exits, so one that has exited may linger as a zombie.
+fn running(pid: i32) -> bool {
+    let output = Command::new("ps")
+        .args(["-o", "stat=", "-p", &pid.to_string()])
+        .output()
+        .unwrap();
+    let stat = String::from_utf8_lossy(&output.stdout);
+    let stat = stat.trim();
+    !stat.is_empty() && !stat.starts_with('Z')
+}
+
+fn state_pid(path: &Path) -> i32 {
+    let state: serde_json::Value = serde_json::from_slice(&fs::read(path).unwrap()).unwrap();
+    state["pid"].as_i64().unwrap() as i32
+}
+
+#[test]
+fn concurrent_starts_leave_one_daemon() {
+    let sandbox = Sandbox::new("concurrent-start");
+
+    let starts: Vec<_> = (0..STARTS)
+        .map(|_| {
+            sandbox
+                .tock(&["start", "5"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    let pids: Vec<i32> = starts
+        .into_iter()
+        .map(|child| started_pid(&child.wait_with_output().unwrap()))
+        .collect();
+
+    let survivor = state_pid(&sandbox.data_file("state.json"));
+    assert!(
+        pids.contains(&survivor),
+        "state.json names pid {survivor}, not one of {pids:?}"
+    );
+
+    // Replaced daemons answer `stop` first and exit once their hooks are done.
+    let give_up = Instant::now() + Duration::from_secs(10);
+    let mut alive: Vec<i32> = pids.iter().copied().filter(|&pid| running(pid)).collect();
+    while alive.len() > 1 && Instant::now() < give_up {
+        thread::sleep(Duration::from_millis(50));
+        alive.retain(|&pid| running(pid));
+    }
+    assert_eq!(alive, vec![survivor]);
+
+    let log = fs::read_to_string(sandbox.data_file("log.csv")).unwrap();
+    let replaced = log
+        .lines()
+        .filter(|line| line.contains(",replaced_by_new_timer,"))
+        .count();
+    assert_eq!(replaced, STARTS - 1, "log:\n{log}");
+
+    let status = sandbox.tock(&["status", "--json"]).output().unwrap();
+    assert!(status.status.success());
+    let status: serde_json::Value = serde_json::from_slice(&status.stdout).unwrap();
+    assert_eq!(status["pid"].as_i64(), Some(survivor as i64));
+}
@@ -0,0 +1,180 @@
+//! Exercises the freedesktop notification client against a private
+//! `dbus-daemon` with a stub notification server on it. Skipped when
+//! `dbus-daemon` isn't installed.
+
+#[allow(dead_code)]
+#[path = "../src/dbus.rs"]
+mod dbus;
+
+use dbus::{Connection, DesktopNotification, Message, MessageType, Urgency, Value};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+
+struct PrivateBus {
+    child: Child,
+    dir: PathBuf,
+    address: String,
+}
+
+impl PrivateBus {
+    fn start(name: &str) -> Option<PrivateBus> {
+        let dir = std::env::temp_dir().join(format!("tock-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("bus.conf");
+        fs::write(
+            &config,
+            format!(
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path={0}/bus</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+                dir.display()
+            ),
+        )
+        .unwrap();
+
+        let mut child = match Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config.display()))
+            .arg("--nofork")
+            .arg("--print-address=1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("skipping: cannot run dbus-daemon: {e}");
+                return None;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        Some(PrivateBus {
+            child,
+            dir,
+            address: address.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Claims the notification service name, answers one `Notify` call with id
+/// 42 and hands back the arguments it received.
+fn spawn_stub_server(address: String) -> mpsc::Receiver<Vec<Value>> {
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (calls_tx, calls_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut conn = Connection::open(&address).unwrap();
+        let reply = conn
+            .call(
+                Message::method_call(
+                    "org.freedesktop.DBus",
+                    "/org/freedesktop/DBus",
+                    "org.freedesktop.DBus",
+                    "RequestName",
+                ),
+                &[Value::Str(NOTIFICATIONS_NAME.to_string()), Value::U32(4)],
+            )
+            .unwrap();
+        assert_eq!(reply.first().and_then(Value::as_u32), Some(1));
+        ready_tx.send(()).unwrap();
+
+        loop {
+            let call = conn.read_message().unwrap();
+            if call.kind != Some(MessageType::MethodCall)
+                || call.member.as_deref() != Some("Notify")
+            {
+                continue;
+            }
+            let mut reply = Message::new(MessageType::MethodReturn);
+            reply.reply_serial = Some(call.serial);
+            reply.destination = call.sender.clone();
+            conn.send(&reply, &[Value::U32(42)]).unwrap();
+            calls_tx.send(call.body().unwrap()).unwrap();
+            return;
+        }
+    });
+    ready_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    calls_rx
+}
+
+fn notification() -> DesktopNotification<'static> {
+    DesktopNotification {
+        app_name: "tock",
+        icon: "appointment-soon",
+        summary: "Pomodoro",
+        body: "Work finished. Time for a break.",
+        urgency: Urgency::Critical,
+        sound: Some("complete"),
+        expire_timeout_ms: 10_000,
+    }
+}
+
+#[test]
+fn notify_reaches_notification_server() {
+    let Some(bus) = PrivateBus::start("dbus-notify") else {
+        return;
+    };
+    let calls = spawn_stub_server(bus.address.clone());
+
+    let mut conn = Connection::open(&bus.address).unwrap();
+    let id = dbus::notify(&mut conn, &notification()).unwrap();
+    assert_eq!(id, 42);
+
+    let args = calls.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(args.len(), 8);
+    assert_eq!(args[0], Value::Str("tock".to_string()));
+    assert_eq!(args[1], Value::U32(0));
+    assert_eq!(args[2], Value::Str("appointment-soon".to_string()));
+    assert_eq!(args[3], Value::Str("Pomodoro".to_string()));
+    assert_eq!(
+        args[4],
+        Value::Str("Work finished. Time for a break.".to_string())
+    );
+    assert_eq!(args[5], Value::Array("s".to_string(), Vec::new()));
+    assert_eq!(
+        args[6],
+        dbus::string_variant_dict(vec![
+            ("urgency", Value::Byte(2)),
+            ("sound-name", Value::Str("complete".to_string())),
+        ])
+    );
+    assert_eq!(args[7], Value::I32(10_000));
+}
+
+#[test]
+fn notify_reports_missing_server() {
+    let Some(bus) = PrivateBus::start("dbus-missing") else {
+        return;
+    };
+
+    let mut conn = Connection::open(&bus.address).unwrap();
+    let err = dbus::notify(&mut conn, &notification()).unwrap_err();
+    assert!(err.contains("ServiceUnknown"), "unexpected error: {err}");
+}
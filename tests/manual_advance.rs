@@ -0,0 +1,100 @@
+//! Runs a `--manual-advance` sequence through the end of its first phase and
+//! checks that the timer waits for `tock next`, and that the wait is logged as
+//! overtime.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit code of `tock status` while a phase waits for `tock next`.
+const AWAITING_ACK: i32 = 5;
+
+/// Private XDG dirs, so the test never touches a real timer.
+struct Sandbox {
+    dir: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Sandbox {
+        let dir = std::env::temp_dir().join(format!("tock-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("config/tock")).unwrap();
+        fs::create_dir_all(dir.join("runtime")).unwrap();
+        fs::write(
+            dir.join("config/tock/config.toml"),
+            "[notify]\nbackends = [\"none\"]\n",
+        )
+        .unwrap();
+        Sandbox { dir }
+    }
+
+    fn tock(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_tock"))
+            .args(args)
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_RUNTIME_DIR", self.dir.join("runtime"))
+            .env_remove("TOCK_MANUAL_ADVANCE")
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    }
+
+    fn log(&self) -> String {
+        fs::read_to_string(self.dir.join("data/tock/log.csv")).unwrap_or_default()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = self.tock(&["stop"]);
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// The value of `column` in `row`, by the log's header.
+fn column<'a>(log: &'a str, row: &'a str, column: &str) -> &'a str {
+    let header = log.lines().next().unwrap();
+    let i = header.split(',').position(|name| name == column).unwrap();
+    row.split(',').nth(i).unwrap_or("")
+}
+
+#[test]
+fn a_finished_phase_waits_for_next_and_logs_the_overtime() {
+    let sandbox = Sandbox::new("manual-advance");
+    let started = sandbox.tock(&["run-sequence", "1sw 1sb", "--manual-advance"]);
+    assert!(
+        started.status.success(),
+        "{}",
+        String::from_utf8_lossy(&started.stderr)
+    );
+
+    let give_up = Instant::now() + Duration::from_secs(10);
+    let waiting = loop {
+        let code = sandbox.tock(&["status"]).status.code();
+        if code == Some(AWAITING_ACK) || Instant::now() > give_up {
+            break code;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    assert_eq!(waiting, Some(AWAITING_ACK));
+    assert_eq!(sandbox.log(), "", "nothing is logged until `tock next`");
+
+    thread::sleep(Duration::from_millis(1500));
+    let next = sandbox.tock(&["next"]);
+    assert!(
+        next.status.success(),
+        "{}",
+        String::from_utf8_lossy(&next.stderr)
+    );
+    assert_eq!(sandbox.tock(&["status"]).status.code(), Some(0));
+
+    let log = sandbox.log();
+    let row = log.lines().nth(1).unwrap_or_else(|| panic!("log:\n{log}"));
+    assert_eq!(column(&log, row, "mode"), "work");
+    assert_eq!(column(&log, row, "end_reason"), "completed");
+    let overtime: u64 = column(&log, row, "overtime_secs").parse().unwrap();
+    assert!(overtime >= 1, "log:\n{log}");
+}